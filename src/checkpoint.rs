@@ -0,0 +1,94 @@
+//! The uploads a `batch` or `sync` run has finished, written down as it goes, so a
+//! run that was interrupted can be started again without repeating them.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::error::Result;
+
+/// One finished upload, as a line of the checkpoint file.
+#[derive(Serialize, Deserialize, Debug)]
+struct Done {
+    local: PathBuf,
+    remote: String,
+    size: u64,
+    /// Modification time in seconds and nanoseconds since the Unix epoch.
+    mtime: (u64, u32),
+}
+
+/// A checkpoint file of JSON lines, one per finished upload, appended to as each
+/// upload succeeds.
+#[derive(Debug)]
+pub struct Checkpoint {
+    path: PathBuf,
+    /// Size and modification time of each finished local file, by local and remote path.
+    done: HashMap<(PathBuf, String), (u64, (u64, u32))>,
+    file: File,
+}
+
+pub(crate) fn stat(local: &Path) -> io::Result<(u64, (u64, u32))> {
+    let metadata = fs::metadata(local)?;
+    let since = metadata.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default();
+    Ok((metadata.len(), (since.as_secs(), since.subsec_nanos())))
+}
+
+impl Checkpoint {
+    /// Name of the checkpoint `sync` keeps in the local directory, which it does not upload.
+    pub const FILE_NAME: &'static str = ".alist-cli-checkpoint.jsonl";
+
+    /// Open the checkpoint at `path`, creating it if missing. A line that cannot be
+    /// read, such as one cut short by a crash, is ignored.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let done = match fs::read_to_string(&path) {
+            Ok(contents) => contents
+                .lines()
+                .filter_map(|line| serde_json::from_str::<Done>(line).ok())
+                .map(|done| ((done.local, done.remote), (done.size, done.mtime)))
+                .collect(),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(err) => return Err(err.into()),
+        };
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Checkpoint { path, done, file })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether an earlier run uploaded `local` to `remote`, and the file has not
+    /// changed since.
+    pub fn is_done(&self, local: &Path, remote: &str) -> bool {
+        self.done
+            .get(&(local.to_path_buf(), remote.to_string()))
+            .is_some_and(|recorded| stat(local).is_ok_and(|now| now == *recorded))
+    }
+
+    /// Note that `local` has been uploaded to `remote`, writing it out at once.
+    pub fn record(&mut self, local: &Path, remote: &str) -> Result<()> {
+        let (size, mtime) = stat(local)?;
+        let done = Done {
+            local: local.to_path_buf(),
+            remote: remote.to_string(),
+            size,
+            mtime,
+        };
+        let mut line = serde_json::to_vec(&done)?;
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        self.done.insert((done.local, done.remote), (size, mtime));
+        Ok(())
+    }
+
+    /// Delete the checkpoint once the run is complete, so the next one starts afresh.
+    pub fn remove(self) -> Result<()> {
+        drop(self.file);
+        fs::remove_file(&self.path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+use alist_cli::{AlistError, Result};
+
+use super::{connect, select_fields};
+use crate::cli::{ApiArgs, GlobalOpts};
+
+pub async fn cmd_api(opts: &GlobalOpts, args: &ApiArgs) -> Result<()> {
+    // The query string would be lost splitting a URL into server and path.
+    let (target, query) = match args.path.split_once('?') {
+        Some((target, query)) => (target, Some(query)),
+        None => (args.path.as_str(), None),
+    };
+    let (client, path) = if target.starts_with("http://") || target.starts_with("https://") {
+        connect(opts, target).await?
+    } else {
+        let (client, _) = connect(opts, "/").await?;
+        (client, format!("/{}", target.trim_start_matches('/')))
+    };
+    let path = match query {
+        Some(query) => format!("{}?{}", path, query),
+        None => path,
+    };
+
+    let (status, body) = client.request_raw(args.method.clone(), &path, args.data.as_ref()).await?;
+    match serde_json::from_str::<serde_json::Value>(&body) {
+        Ok(value) if !args.select_fields.is_empty() => {
+            let selected = select_fields(&value, &args.select_fields, args.strict_select)?;
+            println!("{}", serde_json::to_string_pretty(&selected)?);
+        }
+        Ok(value) if !args.raw => println!("{}", serde_json::to_string_pretty(&value)?),
+        _ => println!("{}", body),
+    }
+    if !status.is_success() {
+        return Err(AlistError::Api {
+            code: status.as_u16(),
+            message: format!("{} {} answered HTTP {}", args.method, path, status),
+        });
+    }
+    Ok(())
+}
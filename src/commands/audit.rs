@@ -0,0 +1,112 @@
+//! The `--log-file` record of a run: one JSON line per command appended to the file,
+//! with what happened to each file and the totals, whatever the terminal showed.
+
+use alist_cli::AlistError;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// Whether files are being recorded, which only `--log-file` needs.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static FILES: Mutex<Vec<FileRecord>> = Mutex::new(Vec::new());
+
+/// What became of one file.
+#[derive(Serialize)]
+struct FileRecord {
+    path: String,
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize, Default)]
+struct Totals {
+    files: usize,
+    ok: usize,
+    skipped: usize,
+    failed: usize,
+    bytes: u64,
+}
+
+/// One line of the log file.
+#[derive(Serialize)]
+struct RunRecord<'a> {
+    timestamp: String,
+    command: &'a [String],
+    duration_ms: u128,
+    exit_status: i32,
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    totals: Totals,
+    files: &'a [FileRecord],
+}
+
+/// Start recording what happens to each file, for [`write`] to log at the end.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Note what became of the file at `path`: `ok`, `skipped` or `failed`, or another
+/// status of the command's `--json` output such as `started`.
+pub fn record_file(path: &str, status: &'static str, size: Option<u64>, error: Option<&AlistError>) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    FILES.lock().expect("log file lock").push(FileRecord {
+        path: path.to_string(),
+        status,
+        size,
+        error: error.map(ToString::to_string),
+    });
+}
+
+/// Append the record of the run that started `started` ago and ended in `exit_status`
+/// to the log file at `path`.
+pub fn write(
+    path: &Path,
+    started: SystemTime,
+    exit_status: i32,
+    error: Option<&AlistError>,
+) -> io::Result<()> {
+    let files = FILES.lock().expect("log file lock");
+    let mut totals = Totals {
+        files: files.len(),
+        ..Totals::default()
+    };
+    for file in files.iter() {
+        match file.status {
+            "skipped" => totals.skipped += 1,
+            "failed" => totals.failed += 1,
+            _ => totals.ok += 1,
+        }
+        totals.bytes += file.size.unwrap_or(0);
+    }
+    let command: Vec<String> = std::env::args().collect();
+    let record = RunRecord {
+        timestamp: DateTime::<Utc>::from(started).to_rfc3339(),
+        command: &command,
+        duration_ms: started.elapsed().unwrap_or(Duration::ZERO).as_millis(),
+        exit_status,
+        status: match error {
+            None => "ok",
+            Some(AlistError::Aborted) => "aborted",
+            Some(_) => "error",
+        },
+        error: error.map(ToString::to_string),
+        totals,
+        files: &files,
+    };
+    let mut line = serde_json::to_vec(&record)?;
+    line.push(b'\n');
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(&line)?;
+    file.sync_all()
+}
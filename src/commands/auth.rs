@@ -0,0 +1,192 @@
+use alist_cli::credentials;
+use alist_cli::model::{PublicSettings, UserInfo};
+use alist_cli::token_cache::TokenCache;
+use alist_cli::{AlistError, Result};
+
+use serde::Serialize;
+use tracing::warn;
+
+use super::{account_password, build_client, connect, print_json, split_remote};
+use crate::cli::GlobalOpts;
+
+/// `--json` result of `logout`.
+#[derive(Serialize)]
+struct LogoutResult<'a> {
+    status: &'static str,
+    server: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    username: Option<&'a str>,
+    /// Whether there was a cached token to remove.
+    removed: bool,
+    /// Whether the server invalidated the token.
+    revoked: bool,
+}
+
+/// `--json` result of `info`.
+#[derive(Serialize)]
+struct InfoResult<'a> {
+    status: &'static str,
+    server: &'a str,
+    #[serde(flatten)]
+    settings: &'a PublicSettings,
+}
+
+pub async fn cmd_info(opts: &GlobalOpts, alist_url: Option<&str>) -> Result<()> {
+    let (base_url, _) = split_remote(opts, alist_url.unwrap_or("/"))?;
+    let client = build_client(opts, base_url)?;
+    let settings = client.public_settings().await?;
+
+    if opts.json {
+        return print_json(&InfoResult {
+            status: "ok",
+            server: client.base_url(),
+            settings: &settings,
+        });
+    }
+    println!("Server:         {}", client.base_url());
+    println!("Version:        {}", settings.version);
+    println!("Site title:     {}", settings.site_title);
+    println!("Allow indexed:  {}", settings.allow_indexed);
+    println!("Allow mounted:  {}", settings.allow_mounted);
+    // Feature switches differ between versions; show the yes/no ones as sent.
+    for (key, value) in &settings.other {
+        if let Some(flag) = value.as_str().filter(|value| *value == "true" || *value == "false") {
+            println!("{:<15} {}", format!("{}:", key), flag);
+        }
+    }
+    Ok(())
+}
+
+/// `--json` result of `me`.
+#[derive(Serialize)]
+struct MeResult<'a> {
+    status: &'static str,
+    #[serde(flatten)]
+    user: &'a UserInfo,
+    role_name: &'static str,
+    permissions: Vec<&'static str>,
+}
+
+pub async fn cmd_me(opts: &GlobalOpts, alist_url: Option<&str>) -> Result<()> {
+    let (client, _) = connect(opts, alist_url.unwrap_or("/")).await?;
+    let user = client.me().await?;
+
+    if opts.json {
+        return print_json(&MeResult {
+            status: "ok",
+            user: &user,
+            role_name: user.role_name(),
+            permissions: user.permission_names(),
+        });
+    }
+    println!("Username:    {}", user.username);
+    println!("Role:        {}", user.role_name());
+    println!("Base path:   {}", user.base_path);
+    println!("Permissions: {:#b} ({})", user.permission, user.permission_names().join(", "));
+    if user.disabled {
+        println!("This account is disabled");
+    }
+    Ok(())
+}
+
+/// `--json` result of `login`.
+#[derive(Serialize)]
+struct LoginResult<'a> {
+    status: &'static str,
+    server: &'a str,
+    username: &'a str,
+    /// Whether the password was stored in the system keyring.
+    saved: bool,
+}
+
+/// Log in with the password, ignoring any cached token, cache the new token, and
+/// with `save` store the password in the system keyring.
+pub async fn cmd_login(opts: &GlobalOpts, alist_url: Option<&str>, save: bool) -> Result<()> {
+    if opts.token.is_some() {
+        return Err(AlistError::Config(
+            "login needs a username and password, not --token".to_string(),
+        ));
+    }
+    let (base_url, _) = split_remote(opts, alist_url.unwrap_or("/"))?;
+    let username = opts.username()?;
+    let password = account_password(opts, &base_url, username)?;
+    let mut client = build_client(opts, base_url.clone())?
+        .with_otp_prompt(|| Ok(rpassword::prompt_password("Two-factor code: ")?));
+    client.login_with_otp(username, &password, opts.otp.as_deref()).await?;
+    if let (false, Some(token)) = (opts.no_cache, client.token()) {
+        let mut cache = TokenCache::load();
+        cache.insert(client.base_url(), username, &token);
+        cache.save()?;
+    }
+    if save {
+        credentials::save_password(&base_url, username, &password)?;
+    }
+
+    if opts.json {
+        return print_json(&LoginResult {
+            status: "ok",
+            server: &base_url,
+            username,
+            saved: save,
+        });
+    }
+    println!("Logged in as {} on {}", username, base_url);
+    if save {
+        println!("Saved the password in the system keyring; pass --use-keyring to log in with it");
+    }
+    Ok(())
+}
+
+/// Invalidate the cached token, or the one given with `--token`, on the server and
+/// drop it from the cache. Not having a token to log out is no error.
+pub async fn cmd_logout(opts: &GlobalOpts, alist_url: Option<&str>) -> Result<()> {
+    let (base_url, _) = split_remote(opts, alist_url.unwrap_or("/"))?;
+    // A token given on its own needs no username; there is no cache entry to drop then.
+    let username = match (&opts.token, &opts.username) {
+        (Some(_), None) => None,
+        _ => Some(opts.username()?),
+    };
+    let mut cache = TokenCache::load();
+    let token = opts
+        .token
+        .clone()
+        .or_else(|| username.and_then(|username| cache.get(&base_url, username)).map(str::to_string));
+
+    let mut revoked = false;
+    if let Some(token) = token {
+        let mut client = build_client(opts, base_url.clone())?;
+        client.set_token(token);
+        match client.logout().await {
+            Ok(true) => revoked = true,
+            Ok(false) => {
+                eprintln!("The server cannot invalidate tokens; the token stays valid until it expires")
+            }
+            // An expired or already revoked token fails like this, and is no loss.
+            Err(AlistError::Auth { .. }) => {}
+            Err(err) => warn!("Could not invalidate the token on the server: {}", err),
+        }
+    }
+    let removed = username.is_some_and(|username| cache.remove(&base_url, username));
+    if removed {
+        cache.save()?;
+    }
+
+    if opts.json {
+        return print_json(&LogoutResult {
+            status: "ok",
+            server: &base_url,
+            username,
+            removed,
+            revoked,
+        });
+    }
+    if revoked {
+        println!("Invalidated the token on {}", base_url);
+    }
+    match username {
+        Some(username) if removed => println!("Removed cached token for {} on {}", username, base_url),
+        Some(username) => println!("No cached token for {} on {}", username, base_url),
+        None => {}
+    }
+    Ok(())
+}
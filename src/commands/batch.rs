@@ -0,0 +1,116 @@
+use alist_cli::checkpoint::Checkpoint;
+use alist_cli::client::Transport;
+use alist_cli::path::split_parent;
+use alist_cli::{AlistError, Result};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use super::upload::{check_webdav, local_metadata, Job, Plan, Uploader};
+use super::{connect_many, connect_webdav_many};
+use crate::cli::{BatchArgs, GlobalOpts};
+
+/// One upload listed in a manifest.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct UploadJob {
+    /// A local file, or a directory to mirror.
+    local: PathBuf,
+    /// Where it goes: a full URL, or a path on the profile's server.
+    remote: String,
+    /// Replace (`true`) or refuse to replace (`false`) an existing remote file,
+    /// instead of following `--no-overwrite` and `--skip-existing`.
+    #[serde(default)]
+    overwrite: Option<bool>,
+    /// Create the remote parent directory first, instead of following `--mkdir`.
+    #[serde(default)]
+    mkdir: Option<bool>,
+}
+
+/// The TOML form of a manifest: an `[[upload]]` table per job.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    #[serde(default)]
+    upload: Vec<UploadJob>,
+}
+
+/// Read the jobs of the manifest at `path`, as JSON if it ends in `.json` and TOML otherwise.
+fn load_manifest(path: &Path) -> Result<Vec<UploadJob>> {
+    let text = std::fs::read_to_string(path)?;
+    let jobs = if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json")) {
+        serde_json::from_str(&text).map_err(|err| err.to_string())
+    } else {
+        toml::from_str::<Manifest>(&text)
+            .map(|manifest| manifest.upload)
+            .map_err(|err| err.to_string())
+    };
+    jobs.map_err(|err| AlistError::Config(format!("{}: {}", path.display(), err)))
+}
+
+/// Upload everything a manifest lists, all to the same server and with up to
+/// `--concurrency` transfers at once, failing at the end if any upload failed.
+pub async fn cmd_batch(opts: &GlobalOpts, args: &BatchArgs) -> Result<()> {
+    let jobs = load_manifest(&args.manifest)?;
+    if jobs.is_empty() {
+        return Err(AlistError::Config(format!("{} lists no uploads", args.manifest.display())));
+    }
+    let remotes: Vec<String> = jobs.iter().map(|job| job.remote.clone()).collect();
+    let (client, paths) = match args.options.transport {
+        Transport::Api => connect_many(opts, &remotes).await?,
+        Transport::WebDav => {
+            check_webdav(&args.options)?;
+            if let Some(job) = jobs.iter().find(|job| job.overwrite == Some(false)) {
+                return Err(AlistError::Config(format!(
+                    "`overwrite = false` for {} is not supported with --transport webdav",
+                    job.remote
+                )));
+            }
+            connect_webdav_many(opts, &remotes)?
+        }
+    };
+    let uploader = Uploader::new(opts, &args.options, client)?;
+
+    let base = args.manifest.parent().unwrap_or(Path::new(""));
+    let mut plan = Plan::default();
+    for (job, remote_path) in jobs.into_iter().zip(paths) {
+        let local = base.join(&job.local);
+        if local_metadata(&local).await?.is_dir() {
+            uploader.collect_dir(&local, &remote_path, job.overwrite, &mut plan)?;
+            continue;
+        }
+        if job.mkdir.unwrap_or(args.options.mkdir) {
+            plan.dirs.push(split_parent(&remote_path).0.to_string());
+        }
+        plan.jobs.push(Job {
+            local,
+            remote: remote_path,
+            overwrite: job.overwrite,
+        });
+    }
+    let mut seen = HashSet::new();
+    plan.dirs.retain(|dir| seen.insert(dir.clone()));
+
+    uploader.add_suffixes(&mut plan.jobs);
+    uploader.check_sizes(&plan.jobs).await?;
+    if opts.dry_run {
+        return plan.print(opts);
+    }
+    let checkpoint = match (&args.resume_from, args.resume) {
+        (Some(path), _) => Some(path.clone()),
+        (None, true) => {
+            let mut path = args.manifest.clone().into_os_string();
+            path.push(".checkpoint");
+            Some(PathBuf::from(path))
+        }
+        (None, false) => None,
+    };
+    let uploader = match checkpoint {
+        Some(path) => uploader.with_checkpoint(Checkpoint::open(path)?),
+        None => uploader,
+    };
+    for dir in &plan.dirs {
+        uploader.client.ensure_dir(dir).await?;
+    }
+    uploader.run(plan.jobs).await
+}
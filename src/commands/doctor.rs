@@ -0,0 +1,184 @@
+use alist_cli::{AlistError, Result};
+use serde::Serialize;
+use std::fmt::Display;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use url::Url;
+
+use super::{build_client, connect, print_json, split_remote};
+use crate::cli::GlobalOpts;
+
+/// `--json` result of one step of `doctor`.
+#[derive(Serialize)]
+struct StepResult<'a> {
+    /// `ok`, `failed` or `skipped`.
+    status: &'static str,
+    step: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    elapsed_ms: Option<u64>,
+    /// What the step found, or why it failed or was skipped.
+    detail: String,
+}
+
+/// The steps run so far, and how many failed.
+struct Report<'a> {
+    opts: &'a GlobalOpts,
+    steps: usize,
+    failed: usize,
+}
+
+impl Report<'_> {
+    /// Run the step `name`, report how it went and return its result if it passed.
+    async fn step<T, D: Display>(
+        &mut self,
+        name: &str,
+        run: impl Future<Output = Result<T>>,
+        detail: impl FnOnce(&T) -> D,
+    ) -> Result<Option<T>> {
+        let started = Instant::now();
+        let result = run.await;
+        let elapsed = started.elapsed();
+        self.steps += 1;
+        match result {
+            Ok(value) => {
+                self.print("ok", name, Some(elapsed), detail(&value).to_string())?;
+                Ok(Some(value))
+            }
+            Err(err) => {
+                self.failed += 1;
+                self.print("failed", name, Some(elapsed), err.to_string())?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn skip(&self, name: &str, reason: &str) -> Result<()> {
+        self.print("skipped", name, None, reason.to_string())
+    }
+
+    fn print(
+        &self,
+        status: &'static str,
+        step: &str,
+        elapsed: Option<Duration>,
+        detail: String,
+    ) -> Result<()> {
+        if self.opts.json {
+            return print_json(&StepResult {
+                status,
+                step,
+                elapsed_ms: elapsed.map(|elapsed| elapsed.as_millis() as u64),
+                detail,
+            });
+        }
+        let elapsed = elapsed.map_or(String::new(), |elapsed| format!("{} ms", elapsed.as_millis()));
+        println!("{:<8} {:<18} {:>8}  {}", status, step, elapsed, detail);
+        Ok(())
+    }
+}
+
+/// Go through connecting to and logging in to the server one step at a time, and
+/// report each, so a failure can be told apart from the others. Later steps are
+/// skipped once one they depend on has failed.
+pub async fn cmd_doctor(opts: &GlobalOpts, alist_url: Option<&str>) -> Result<()> {
+    let (base_url, _) = split_remote(opts, alist_url.unwrap_or("/"))?;
+    let url = Url::parse(&base_url).map_err(|err| AlistError::InvalidUrl(format!("{}: {}", base_url, err)))?;
+    let host = url.host_str().unwrap_or_default().to_string();
+    let port = url.port_or_known_default().unwrap_or(80);
+    let mut report = Report {
+        opts,
+        steps: 0,
+        failed: 0,
+    };
+
+    let resolve = async {
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), port)).await?.collect();
+        Ok(addrs)
+    };
+    let addrs = report
+        .step("resolve host", resolve, |addrs: &Vec<SocketAddr>| {
+            let ips: Vec<String> = addrs.iter().map(|addr| addr.ip().to_string()).collect();
+            format!("{} -> {}", host, ips.join(", "))
+        })
+        .await?;
+
+    match addrs.as_deref() {
+        Some([addr, ..]) => {
+            let addr = *addr;
+            let tcp = async {
+                let connecting = tokio::net::TcpStream::connect(addr);
+                let stream = match opts.connect_timeout {
+                    Some(secs) => tokio::time::timeout(Duration::from_secs(secs), connecting)
+                        .await
+                        .map_err(|_| std::io::Error::from(std::io::ErrorKind::TimedOut))??,
+                    None => connecting.await?,
+                };
+                Ok(stream.peer_addr()?)
+            };
+            report.step("tcp connect", tcp, |peer| format!("connected to {}", peer)).await?;
+        }
+        _ => report.skip("tcp connect", "no address to connect to")?,
+    }
+    if opts.proxy.is_some() {
+        eprintln!("Note: the steps above bypass --proxy; the ones below go through it");
+    }
+
+    let client = build_client(opts, base_url.clone())?;
+    let transport = if url.scheme() == "https" { "tls and http" } else { "http" };
+    let answered = report
+        .step(transport, client.probe(), |status| format!("{} answered {}", base_url, status))
+        .await?
+        .is_some();
+    if !answered {
+        for step in ["api", "login", "token"] {
+            report.skip(step, "the server cannot be reached")?;
+        }
+        return finish(&report);
+    }
+
+    report
+        .step("api", client.public_settings(), |settings| {
+            format!("Alist {} ({})", settings.version, settings.site_title)
+        })
+        .await?;
+
+    if opts.token.is_none() && opts.username.is_none() {
+        report.skip("login", "no credentials given")?;
+        report.skip("token", "no credentials given")?;
+        return finish(&report);
+    }
+    // Always log in afresh, so a cached token cannot hide a wrong password.
+    let fresh = GlobalOpts {
+        no_cache: opts.token.is_none(),
+        ..opts.clone()
+    };
+    let login_name = if opts.token.is_some() { "token given" } else { "login" };
+    let client = report
+        .step(login_name, connect(&fresh, &base_url), |_| match &opts.username {
+            Some(username) if opts.token.is_none() => format!("logged in as {}", username),
+            _ => "using --token".to_string(),
+        })
+        .await?;
+    match client {
+        Some((client, _)) => {
+            report
+                .step("token", client.me(), |user| {
+                    format!("the token belongs to {} ({})", user.username, user.role_name())
+                })
+                .await?;
+        }
+        None => report.skip("token", "not logged in")?,
+    }
+    finish(&report)
+}
+
+fn finish(report: &Report) -> Result<()> {
+    if report.failed == 0 {
+        return Ok(());
+    }
+    Err(AlistError::PartialFailure {
+        failed: report.failed,
+        total: report.steps,
+    })
+}
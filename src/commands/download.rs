@@ -0,0 +1,484 @@
+use alist_cli::client::RawDownload;
+use alist_cli::crypt::{self, Passphrase};
+use alist_cli::model::{FileEntry, GetData};
+use alist_cli::path::strip_prefix;
+use alist_cli::progress::{ProgressFormat, TotalProgress};
+use alist_cli::{AlistClient, AlistError, Result};
+use futures_util::stream::{self, StreamExt};
+use indicatif::ProgressBar;
+use std::io::{IsTerminal, Write};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{ready, Context, Poll};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWrite;
+
+use super::audit;
+use super::sync::list_tree;
+use super::{
+    connect, connect_many, multi_progress, print_json, progress_refresh, read_passphrase, split_remote,
+    total_progress, transfer_progress,
+};
+use crate::cli::{DownloadArgs, GlobalOpts, LinkArgs, OnError};
+
+/// `--json` result of `download`.
+#[derive(Serialize)]
+struct DownloadResult<'a> {
+    status: &'static str,
+    path: &'a Path,
+    size: u64,
+}
+
+/// `--json` result of a file of `download --output-dir` that failed.
+#[derive(Serialize)]
+struct DownloadFailure<'a> {
+    status: &'static str,
+    path: &'a str,
+    error: String,
+}
+
+/// Resolve `alist_url` and fetch its metadata, refusing directories, along with the
+/// URL to download it from, signed with `sign` if given.
+async fn resolve(
+    opts: &GlobalOpts,
+    alist_url: &str,
+    sign: Option<&str>,
+) -> Result<(AlistClient, GetData, String)> {
+    let (client, remote_file_path) = connect(opts, alist_url).await?;
+    let info = client.get(&remote_file_path).await?;
+    if info.is_dir {
+        return Err(AlistError::InvalidPath(format!("{} is a directory", remote_file_path)));
+    }
+    let url = client.download_url(&remote_file_path, &info, sign);
+    Ok((client, info, url))
+}
+
+/// The passphrase to decrypt with, asked for once per command with `--decrypt`.
+fn passphrase(args: &DownloadArgs) -> Result<Option<Passphrase>> {
+    args.decrypt.then(|| read_passphrase(false)).transpose()
+}
+
+/// `path` without the `.enc` suffix of an encrypted file, when decrypting it.
+fn decrypted_path(path: PathBuf, decrypt: bool) -> PathBuf {
+    let encrypted = path.extension().is_some_and(|extension| extension == crypt::EXTENSION);
+    if decrypt && encrypted {
+        path.with_extension("")
+    } else {
+        path
+    }
+}
+
+pub async fn cmd_download(opts: &GlobalOpts, args: &DownloadArgs) -> Result<()> {
+    if let Some(output_dir) = &args.output_dir {
+        return download_into(opts, args, output_dir).await;
+    }
+    let [alist_url, local_file] = args.args.as_slice() else {
+        return Err(AlistError::Config(
+            "give a remote path and a local destination, or --output-dir".to_string(),
+        ));
+    };
+    if local_file == "-" {
+        let passphrase = passphrase(args)?;
+        return cmd_cat(opts, alist_url, args.tee.as_deref(), args.sign.as_deref(), passphrase).await;
+    }
+    if args.tee.is_some() {
+        return Err(AlistError::Config("--tee only works when downloading to stdout (`-`)".to_string()));
+    }
+    let passphrase = passphrase(args)?;
+    let (client, info, url) = resolve(opts, alist_url, args.sign.as_deref()).await?;
+
+    let mut local_path = PathBuf::from(local_file);
+    if tokio::fs::metadata(&local_path).await.is_ok_and(|meta| meta.is_dir()) {
+        local_path = decrypted_path(local_path.join(&info.name), args.decrypt);
+    }
+
+    // Resume a partial download of an earlier run. A local file at least as large
+    // as the remote one is either complete or something else, so start over. A
+    // decrypted file can only be written from the start.
+    let existing = tokio::fs::metadata(&local_path).await.map_or(0, |meta| meta.len());
+    let offset = if existing < info.size && passphrase.is_none() { existing } else { 0 };
+    let download = client.open_raw(&url, offset).await?.decrypted(passphrase);
+    fetch(opts, &info, download, &local_path, None).await
+}
+
+/// Write `download` of `info` into `local_path`, appending to it if the download
+/// continues a partial one, and report the result. With `total`, the download
+/// counts towards the bar of all those running at the same time.
+async fn fetch(
+    opts: &GlobalOpts,
+    info: &GetData,
+    download: RawDownload,
+    local_path: &Path,
+    total: Option<&TotalProgress>,
+) -> Result<()> {
+    let name = local_path.to_string_lossy();
+    let progress = match total {
+        Some(total) => total.transfer(Some(info.size)),
+        None => transfer_progress(opts, &name, Some(info.size)),
+    };
+    let say = |line: String| match total {
+        Some(total) => total.suspend(|| println!("{}", line)),
+        None => println!("{}", line),
+    };
+    let mut file = if download.start > 0 {
+        if !opts.json {
+            eprintln!("Resuming {} from byte {}", local_path.display(), download.start);
+        }
+        OpenOptions::new().append(true).open(&local_path).await?
+    } else {
+        File::create(&local_path).await?
+    };
+    let written = download.start + download.write_to(&mut file, &progress).await?;
+    audit::record_file(&local_path.to_string_lossy(), "ok", Some(written), None);
+    if opts.json {
+        print_json(&DownloadResult {
+            status: "ok",
+            path: local_path,
+            size: written,
+        })?;
+    } else {
+        say(format!("Downloaded {} bytes to {}", written, local_path.display()));
+    }
+    Ok(())
+}
+
+/// The remote paths listed in `path`, one per line, leaving out blank lines and
+/// `#` comments.
+fn read_path_list(path: &Path) -> Result<Vec<String>> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|err| AlistError::Config(format!("cannot read {}: {}", path.display(), err)))?;
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Download every remote argument under `output_dir`: files by their name, and
+/// directories with everything below them. Nothing is fetched if any destination
+/// exists and `--overwrite` was not given.
+async fn download_into(opts: &GlobalOpts, args: &DownloadArgs, output_dir: &Path) -> Result<()> {
+    let mut remotes = args.args.clone();
+    if let Some(list) = &args.from_file {
+        remotes.extend(read_path_list(list)?);
+    }
+    let passphrase = passphrase(args)?;
+    let (client, paths) = connect_many(opts, &remotes).await?;
+    let prefix = args
+        .strip_prefix
+        .as_deref()
+        .map(|prefix| split_remote(opts, prefix).map(|(_, path)| path))
+        .transpose()?;
+    let stripped = |file: &str| -> Result<PathBuf> {
+        let prefix = prefix.as_deref().expect("only called with --strip-prefix");
+        match strip_prefix(file, prefix) {
+            Some(relative) if !relative.is_empty() => Ok(output_dir.join(relative)),
+            _ => Err(AlistError::InvalidPath(format!("{} is not below --strip-prefix {}", file, prefix))),
+        }
+    };
+
+    let mut targets: Vec<(String, PathBuf)> = Vec::new();
+    for path in &paths {
+        let info = client.get(path).await?;
+        if !info.is_dir {
+            let local_path = match prefix {
+                Some(_) => stripped(path)?,
+                None => output_dir.join(&info.name),
+            };
+            targets.push((path.clone(), local_path));
+            continue;
+        }
+        let tree = list_tree(&client, path, args.max_depth).await?;
+        let mut files: Vec<(String, FileEntry)> = tree.files.into_iter().collect();
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        let single = files.len() == 1;
+        for (file, entry) in files {
+            let local_path = if args.flatten || (args.flatten_single && single) {
+                output_dir.join(&entry.name)
+            } else if prefix.is_some() {
+                stripped(&file)?
+            } else {
+                let relative = file[path.len()..].trim_start_matches('/');
+                output_dir.join(&info.name).join(relative)
+            };
+            targets.push((file, local_path));
+        }
+    }
+    for (_, local_path) in &mut targets {
+        *local_path = decrypted_path(std::mem::take(local_path), args.decrypt);
+    }
+
+    let mut sources: HashMap<&Path, &str> = HashMap::new();
+    for (remote_path, local_path) in &targets {
+        if let Some(other) = sources.insert(local_path, remote_path) {
+            return Err(AlistError::Config(format!(
+                "both {} and {} would be downloaded to {}",
+                other,
+                remote_path,
+                local_path.display()
+            )));
+        }
+        if !args.overwrite && tokio::fs::try_exists(local_path).await? {
+            return Err(AlistError::Config(format!(
+                "{} already exists; pass --overwrite to replace it",
+                local_path.display()
+            )));
+        }
+    }
+
+    if let [(remote_path, local_path)] = targets.as_slice() {
+        return download_one(opts, args, &client, remote_path, local_path, passphrase, None).await;
+    }
+    let total = targets.len();
+    let bars = multi_progress(opts);
+    let progress = total_progress(opts, &bars, total);
+    let progress = progress.as_ref();
+    // Set once a file failed with `--on-error abort`, after which no more start.
+    let aborted = &AtomicBool::new(false);
+    let client = &client;
+    let passphrase = &passphrase;
+    let downloads = stream::iter(&targets)
+        .map(|(remote_path, local_path)| async move {
+            if aborted.load(Ordering::Relaxed) {
+                return (remote_path.as_str(), None);
+            }
+            let result =
+                download_one(opts, args, client, remote_path, local_path, passphrase.clone(), progress).await;
+            if result.is_err() && opts.on_error == OnError::Abort {
+                aborted.store(true, Ordering::Relaxed);
+            }
+            if let Some(progress) = progress {
+                progress.file_done();
+            }
+            (remote_path.as_str(), Some(result))
+        })
+        .buffer_unordered(args.concurrency.max(1))
+        .collect::<Vec<(&str, Option<Result<()>>)>>();
+    let results = match progress {
+        Some(progress) => {
+            let results = tokio::select! {
+                results = downloads => results,
+                () = progress.refresh(progress_refresh(opts)) => unreachable!("refreshing never ends"),
+            };
+            progress.finish();
+            results
+        }
+        None => downloads.await,
+    };
+
+    let not_started = results.iter().filter(|(_, result)| result.is_none()).count();
+    let failures: Vec<_> = results
+        .into_iter()
+        .filter_map(|(remote_path, result)| result?.err().map(|err| (remote_path, err)))
+        .collect();
+    for (remote_path, err) in &failures {
+        audit::record_file(remote_path, "failed", None, Some(err));
+    }
+    if opts.json {
+        for (remote_path, err) in &failures {
+            print_json(&DownloadFailure {
+                status: "failed",
+                path: remote_path,
+                error: err.to_string(),
+            })?;
+        }
+    } else {
+        println!("Downloaded {} of {} files", total - failures.len() - not_started, total);
+        if not_started > 0 {
+            println!("Stopped after a failure; {} files were not started", not_started);
+        }
+        for (remote_path, err) in &failures {
+            eprintln!("Failed {}: {}", remote_path, err);
+        }
+    }
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(AlistError::PartialFailure {
+            failed: failures.len(),
+            total,
+        })
+    }
+}
+
+/// Download the file at `remote_path` into `local_path`, decrypting it with `passphrase`.
+async fn download_one(
+    opts: &GlobalOpts,
+    args: &DownloadArgs,
+    client: &AlistClient,
+    remote_path: &str,
+    local_path: &Path,
+    passphrase: Option<Passphrase>,
+    total: Option<&TotalProgress>,
+) -> Result<()> {
+    if let Some(parent) = local_path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let info = client.get(remote_path).await?;
+    let url = client.download_url(remote_path, &info, args.sign.as_deref());
+    let download = client.open_raw(&url, 0).await?.decrypted(passphrase);
+    fetch(opts, &info, download, local_path, total).await
+}
+
+/// Stream a remote file to stdout. Progress goes to stderr, and a bar only when stdout
+/// is a terminal, so piped output is never mixed with it. With a `passphrase`, the
+/// file is decrypted as it streams.
+pub async fn cmd_cat(
+    opts: &GlobalOpts,
+    alist_url: &str,
+    tee: Option<&Path>,
+    sign: Option<&str>,
+    passphrase: Option<Passphrase>,
+) -> Result<()> {
+    let (client, info, url) = resolve(opts, alist_url, sign).await?;
+
+    let progress = if opts.progress_format == ProgressFormat::Bar && !std::io::stdout().is_terminal() {
+        ProgressBar::hidden()
+    } else {
+        transfer_progress(opts, &info.name, Some(info.size))
+    };
+    let download = client.open_raw(&url, 0).await?.decrypted(passphrase);
+    let mut stdout = tokio::io::stdout();
+    match tee {
+        Some(path) => {
+            let mut tee = Tee {
+                first: stdout,
+                second: File::create(path).await?,
+                pending: Vec::new(),
+            };
+            download.write_to(&mut tee, &progress).await?;
+            tee.second.sync_all().await?;
+        }
+        None => {
+            download.write_to(&mut stdout, &progress).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes everything to both `first` and `second`, for `--tee`.
+struct Tee<A, B> {
+    first: A,
+    second: B,
+    /// Bytes taken by `first` that `second` has yet to take.
+    pending: Vec<u8>,
+}
+
+impl<A: AsyncWrite + Unpin, B: AsyncWrite + Unpin> Tee<A, B> {
+    /// Hand `second` what `first` has already taken.
+    fn poll_pending(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.pending.is_empty() {
+            let written = ready!(Pin::new(&mut self.second).poll_write(cx, &self.pending))?;
+            if written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.pending.drain(..written);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<A: AsyncWrite + Unpin, B: AsyncWrite + Unpin> AsyncWrite for Tee<A, B> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        ready!(self.poll_pending(cx))?;
+        let written = ready!(Pin::new(&mut self.first).poll_write(cx, buf))?;
+        self.pending.extend_from_slice(&buf[..written]);
+        // Whatever `second` cannot take yet is handed over on the next call.
+        if let Poll::Ready(Err(err)) = self.poll_pending(cx) {
+            return Poll::Ready(Err(err));
+        }
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_pending(cx))?;
+        ready!(Pin::new(&mut self.first).poll_flush(cx))?;
+        Pin::new(&mut self.second).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_pending(cx))?;
+        ready!(Pin::new(&mut self.first).poll_shutdown(cx))?;
+        Pin::new(&mut self.second).poll_shutdown(cx)
+    }
+}
+
+/// `--json` result of `link`.
+#[derive(Serialize)]
+struct LinkResult<'a> {
+    status: &'static str,
+    path: &'a str,
+    url: &'a str,
+}
+
+pub async fn cmd_link(opts: &GlobalOpts, args: &LinkArgs) -> Result<()> {
+    let (client, remote_path) = connect(opts, &args.alist_url).await?;
+    let info = client.get(&remote_path).await?;
+    if info.is_dir {
+        return Err(AlistError::InvalidPath(format!("{} is a directory", remote_path)));
+    }
+
+    let url = if args.d_link {
+        client.d_link(&remote_path, &info, args.sign.as_deref())
+    } else {
+        client.download_url(&remote_path, &info, args.sign.as_deref())
+    };
+
+    if args.clip {
+        copy_to_clipboard(&url)?;
+    }
+    if opts.json {
+        print_json(&LinkResult {
+            status: "ok",
+            path: &remote_path,
+            url: &url,
+        })?;
+    } else {
+        println!("{}", url);
+    }
+    Ok(())
+}
+
+/// Hand `text` to the platform's clipboard tool.
+fn copy_to_clipboard(text: &str) -> Result<()> {
+    let candidates: &[(&str, &[&str])] = if cfg!(target_os = "macos") {
+        &[("pbcopy", &[])]
+    } else if cfg!(windows) {
+        &[("clip", &[])]
+    } else {
+        &[
+            ("wl-copy", &[]),
+            ("xclip", &["-selection", "clipboard"]),
+            ("xsel", &["--clipboard", "--input"]),
+        ]
+    };
+
+    for (program, args) in candidates {
+        let child = Command::new(program)
+            .args(*args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let Ok(mut child) = child else {
+            continue;
+        };
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(text.as_bytes())?;
+        if child.wait()?.success() {
+            return Ok(());
+        }
+    }
+    Err(AlistError::Config(
+        "no clipboard tool found (tried pbcopy, clip, wl-copy, xclip, xsel)".to_string(),
+    ))
+}
@@ -0,0 +1,159 @@
+use alist_cli::{AlistError, Result};
+use glob::Pattern;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tracing::warn;
+use walkdir::WalkDir;
+
+/// Include/exclude rules for the files of a directory walk.
+///
+/// Patterns are matched against the path relative to the walk's root, and those
+/// without a `/` also against the entry's own name, so `.git` or `*.tmp` apply at
+/// any depth.
+#[derive(Debug, Default)]
+pub struct Filter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    /// Only files modified after this are accepted, with `--newer-than`.
+    newer_than: Option<SystemTime>,
+    /// How many directories deep the walk goes below its root, with `--max-depth`.
+    max_depth: Option<usize>,
+}
+
+impl Filter {
+    /// Build a filter from command line patterns, adding the non-empty, non-comment
+    /// lines of `ignore_file` to the excludes.
+    pub fn new(include: &[String], exclude: &[String], ignore_file: Option<&Path>) -> Result<Self> {
+        let mut excludes: Vec<String> = exclude.to_vec();
+        if let Some(ignore_file) = ignore_file {
+            let contents = std::fs::read_to_string(ignore_file)?;
+            excludes.extend(
+                contents
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(|line| line.trim_end_matches('/').to_string()),
+            );
+        }
+        Ok(Filter {
+            include: compile(include)?,
+            exclude: compile(&excludes)?,
+            newer_than: None,
+            max_depth: None,
+        })
+    }
+
+    /// Also leave out files last modified at or before `time`, if given.
+    pub fn with_newer_than(mut self, time: Option<SystemTime>) -> Self {
+        self.newer_than = time;
+        self
+    }
+
+    /// Only walk `depth` directories below the root, if given; 0 keeps the walk to the
+    /// root's own entries.
+    pub fn with_max_depth(mut self, depth: Option<usize>) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Whether the file at `path` was modified recently enough. A file whose time
+    /// cannot be read is kept.
+    fn is_recent(&self, path: &Path) -> bool {
+        let Some(newer_than) = self.newer_than else {
+            return true;
+        };
+        std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .map_or(true, |modified| modified > newer_than)
+    }
+
+    /// Whether the walk should skip `relative` and, for a directory, everything below it.
+    pub fn excludes(&self, relative: &Path) -> bool {
+        self.exclude.iter().any(|pattern| matches(pattern, relative))
+    }
+
+    /// Whether the file at `relative` is uploaded. Excludes win over includes.
+    pub fn accepts_file(&self, relative: &Path) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|pattern| matches(pattern, relative)))
+            && !self.excludes(relative)
+    }
+}
+
+/// Whether `arg` is a glob pattern rather than a literal path.
+pub fn is_glob(arg: &str) -> bool {
+    arg.contains(['*', '?', '['])
+}
+
+fn compile(patterns: &[String]) -> Result<Vec<Pattern>> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            Pattern::new(pattern)
+                .map_err(|err| AlistError::Config(format!("invalid pattern {}: {}", pattern, err)))
+        })
+        .collect()
+}
+
+fn matches(pattern: &Pattern, relative: &Path) -> bool {
+    pattern.matches_path(relative)
+        || (!pattern.as_str().contains('/')
+            && relative
+                .file_name()
+                .is_some_and(|name| pattern.matches(&name.to_string_lossy())))
+}
+
+/// The directories and files below a local root that pass a [`Filter`], as paths
+/// relative to the root, sorted by name with parents before their children.
+#[derive(Debug, Default)]
+pub struct LocalTree {
+    pub dirs: Vec<PathBuf>,
+    pub files: Vec<PathBuf>,
+}
+
+/// Walk `root` recursively, skipping excluded directories entirely.
+///
+/// Symbolic links below `root` are skipped unless `follow_symlinks` is set; when
+/// following them, a link to one of its own parent directories is skipped with a
+/// warning rather than walked forever.
+pub fn walk(root: &Path, filter: &Filter, follow_symlinks: bool) -> Result<LocalTree> {
+    let relative_to = |path: &Path| -> PathBuf {
+        path.strip_prefix(root)
+            .expect("walkdir only yields paths below its root")
+            .to_path_buf()
+    };
+    let mut tree = LocalTree::default();
+    let entries = WalkDir::new(root)
+        .min_depth(1)
+        .max_depth(filter.max_depth.map_or(usize::MAX, |depth| depth + 1))
+        .follow_links(follow_symlinks)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| !filter.excludes(&relative_to(entry.path())));
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) if err.loop_ancestor().is_some() => {
+                let path = err.path().unwrap_or(root).display();
+                warn!("Skipping {}: the link leads back into a parent directory", path);
+                continue;
+            }
+            Err(err) => return Err(io::Error::from(err).into()),
+        };
+        let relative = relative_to(entry.path());
+        if entry.path_is_symlink() && !follow_symlinks {
+            warn!(
+                "Skipping symbolic link {} (use --follow-symlinks to upload its target)",
+                entry.path().display()
+            );
+        } else if entry.file_type().is_dir() {
+            tree.dirs.push(relative);
+        } else if entry.file_type().is_file()
+            && filter.accepts_file(&relative)
+            && filter.is_recent(entry.path())
+        {
+            tree.files.push(relative);
+        }
+    }
+    Ok(tree)
+}
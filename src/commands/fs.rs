@@ -0,0 +1,531 @@
+use alist_cli::model::TaskInfo;
+use alist_cli::path::{join, split_parent};
+use alist_cli::{AlistClient, AlistError, Result};
+use glob::Pattern;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+
+use super::filter::is_glob;
+use super::{confirm, connect, connect_many, print_json, print_planned, wait_for_task};
+use crate::cli::{CpArgs, GlobalOpts, MkdirArgs, MvArgs, OnConflict, RenameArgs, RmArgs};
+
+/// Group remote paths by parent directory, since the remove, move and copy
+/// endpoints each take one directory and a list of names in it.
+pub fn group_by_parent<'a>(paths: impl IntoIterator<Item = &'a str>) -> BTreeMap<&'a str, Vec<String>> {
+    let mut by_parent: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for path in paths {
+        let (parent, name) = split_parent(path);
+        by_parent.entry(parent).or_default().push(name.to_string());
+    }
+    by_parent
+}
+
+/// `--json` result of `mkdir`.
+#[derive(Serialize)]
+struct MkdirResult<'a> {
+    status: &'static str,
+    path: &'a str,
+}
+
+/// `--json` result of `rm`.
+#[derive(Serialize)]
+struct RmResult {
+    status: &'static str,
+    removed: Vec<String>,
+    /// Directories the user declined to remove.
+    skipped: Vec<String>,
+}
+
+/// A remote path and where it ended up.
+#[derive(Serialize)]
+struct Transfer {
+    from: String,
+    to: String,
+}
+
+/// `--json` result of `mv`, `rename` and `cp`.
+#[derive(Serialize)]
+struct TransferResult {
+    status: &'static str,
+    items: Vec<Transfer>,
+    /// Sources left alone by `--on-conflict skip`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    skipped: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tasks: Vec<TaskInfo>,
+}
+
+/// One source of a move or copy, after applying the conflict policy.
+struct Planned {
+    /// Where the source is now.
+    from: String,
+    /// The name it gets in the destination directory.
+    name: String,
+    /// Remove what already has that name in the destination first.
+    overwrite: bool,
+}
+
+impl Planned {
+    /// Whether the source must be renamed before the transfer to end up as `name`.
+    fn renamed(&self) -> bool {
+        split_parent(&self.from).1 != self.name
+    }
+
+    /// The source path at the time of the transfer.
+    fn staged(&self) -> String {
+        join(split_parent(&self.from).0, &self.name)
+    }
+}
+
+/// What a move or copy into a directory will do with each of its sources.
+struct TransferPlan {
+    planned: Vec<Planned>,
+    /// Sources left alone by `--on-conflict skip`.
+    skipped: Vec<String>,
+    /// Sources that cannot go where they would, with the path in the way and why.
+    conflicts: Vec<(String, String, &'static str)>,
+}
+
+impl TransferPlan {
+    /// Fail on the first conflict, before anything is changed.
+    fn check(&self) -> Result<()> {
+        match self.conflicts.first() {
+            Some((_, target, _)) => Err(AlistError::AlreadyExists(target.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Expand the glob patterns that end any of `paths` and check each source against
+/// what `dst_dir` already holds, and against the other sources, applying `policy`.
+/// Only reads from the server; `--dry-run` prints the plan that a real run carries out.
+async fn plan_transfers(
+    client: &AlistClient,
+    policy: OnConflict,
+    paths: &[String],
+    dst_dir: &str,
+) -> Result<TransferPlan> {
+    let mut sources = Vec::new();
+    for path in paths {
+        if is_glob(split_parent(path).1) {
+            sources.extend(expand_remote(client, path).await?.into_iter().map(|(found, _)| found));
+        } else {
+            sources.push(path.clone());
+        }
+    }
+
+    let mut plan = TransferPlan {
+        planned: Vec::new(),
+        skipped: Vec::new(),
+        conflicts: Vec::new(),
+    };
+    // Names in the destination that earlier sources are going to take.
+    let mut claimed = HashSet::new();
+    for path in sources {
+        let (src_dir, name) = split_parent(&path);
+        let target = join(dst_dir, name);
+        let existing = match client.get(&target).await {
+            Ok(info) => Some(info),
+            Err(err) if err.is_not_found() => None,
+            Err(err) => return Err(err),
+        };
+        let clash = claimed.contains(name);
+        let mut item = Planned {
+            from: path.clone(),
+            name: name.to_string(),
+            overwrite: false,
+        };
+        if existing.is_some() || clash {
+            let reason = if clash { "another source goes there too" } else { "already exists" };
+            match policy {
+                OnConflict::Error => {
+                    plan.conflicts.push((path, target, reason));
+                    continue;
+                }
+                OnConflict::Overwrite if clash => {
+                    plan.conflicts.push((path, target, reason));
+                    continue;
+                }
+                OnConflict::Overwrite => item.overwrite = true,
+                OnConflict::Skip => {
+                    plan.skipped.push(path);
+                    continue;
+                }
+                OnConflict::Rename => {
+                    let is_dir = existing.is_some_and(|existing| existing.is_dir);
+                    item.name = free_name(client, name, is_dir, &[src_dir, dst_dir], &claimed).await?;
+                }
+            }
+        }
+        claimed.insert(item.name.clone());
+        plan.planned.push(item);
+    }
+    Ok(plan)
+}
+
+/// How many numbered names [`free_name`] tries before giving up.
+const MAX_NUMBERED_NAMES: u32 = 1000;
+
+/// The first of `name (1)`, `name (2)`, ... that is taken in none of `dirs` and not
+/// `claimed` by another source. For files the number goes before the extension.
+pub(super) async fn free_name(
+    client: &AlistClient,
+    name: &str,
+    is_dir: bool,
+    dirs: &[&str],
+    claimed: &HashSet<String>,
+) -> Result<String> {
+    for n in 1..=MAX_NUMBERED_NAMES {
+        let candidate = match name.rsplit_once('.') {
+            Some((stem, extension)) if !is_dir && !stem.is_empty() => {
+                format!("{} ({}).{}", stem, n, extension)
+            }
+            _ => format!("{} ({})", name, n),
+        };
+        let mut taken = claimed.contains(&candidate);
+        for dir in dirs {
+            taken |= client.exists(&join(dir, &candidate)).await?;
+        }
+        if !taken {
+            return Ok(candidate);
+        }
+    }
+    Err(AlistError::AlreadyExists(format!(
+        "{} and its numbered names up to ({})",
+        join(dirs.last().copied().unwrap_or("/"), name),
+        MAX_NUMBERED_NAMES
+    )))
+}
+
+/// Clear the way for a transfer into `dst_dir`: remove what is being overwritten and
+/// rename the sources that are getting a new name.
+async fn prepare_transfers(client: &AlistClient, planned: &[Planned], dst_dir: &str) -> Result<()> {
+    let overwritten: Vec<String> = planned
+        .iter()
+        .filter(|item| item.overwrite)
+        .map(|item| item.name.clone())
+        .collect();
+    if !overwritten.is_empty() {
+        client.remove(dst_dir, &overwritten).await?;
+    }
+    for item in planned.iter().filter(|item| item.renamed()) {
+        client.rename(&item.from, &item.name).await?;
+    }
+    Ok(())
+}
+
+/// `--json` line of `--dry-run` for a source of a move or copy that cannot go ahead.
+#[derive(Serialize)]
+struct PlannedConflict<'a> {
+    status: &'static str,
+    path: &'a str,
+    to: &'a str,
+    reason: &'a str,
+}
+
+/// Describe a move or copy into `dst_dir` for `--dry-run`: each source and where it
+/// would go, what is skipped, and what conflicts, failing if anything does.
+fn print_transfers(opts: &GlobalOpts, action: &str, plan: &TransferPlan, dst_dir: &str) -> Result<()> {
+    for item in &plan.planned {
+        if item.overwrite {
+            print_planned(opts, "remove", &join(dst_dir, &item.name), None)?;
+        }
+        print_planned(opts, action, &item.from, Some(&join(dst_dir, &item.name)))?;
+    }
+    report_skipped(opts, &plan.skipped, dst_dir);
+    for (path, target, reason) in &plan.conflicts {
+        if opts.json {
+            print_json(&PlannedConflict {
+                status: "conflict",
+                path,
+                to: target,
+                reason,
+            })?;
+        } else {
+            eprintln!("Conflict: {} -> {} ({})", path, target, reason);
+        }
+    }
+    if !opts.json {
+        eprintln!(
+            "{} to {}, {} skipped, {} conflicting",
+            plan.planned.len(),
+            action,
+            plan.skipped.len(),
+            plan.conflicts.len()
+        );
+    }
+    plan.check()
+}
+
+/// Note the sources `--on-conflict skip` left alone.
+fn report_skipped(opts: &GlobalOpts, skipped: &[String], dst_dir: &str) {
+    if !opts.json {
+        for path in skipped {
+            eprintln!("Skipping {} (already exists in {})", path, dst_dir);
+        }
+    }
+}
+
+pub async fn cmd_mkdir(opts: &GlobalOpts, args: &MkdirArgs) -> Result<()> {
+    let (client, remote_dir) = connect(opts, &args.alist_url).await?;
+    if opts.dry_run {
+        return print_planned(opts, "mkdir", &remote_dir, None);
+    }
+    if args.parents {
+        let mut current = String::new();
+        for segment in remote_dir.split('/').filter(|segment| !segment.is_empty()) {
+            current.push('/');
+            current.push_str(segment);
+            client.ensure_dir(&current).await?;
+        }
+    } else {
+        client.mkdir(&remote_dir).await?;
+    }
+
+    if opts.json {
+        print_json(&MkdirResult {
+            status: "ok",
+            path: &remote_dir,
+        })?;
+    }
+    Ok(())
+}
+
+/// `--json` result of `exists`.
+#[derive(Serialize)]
+struct ExistsResult<'a> {
+    status: &'static str,
+    path: &'a str,
+}
+
+pub async fn cmd_exists(opts: &GlobalOpts, alist_url: &str) -> Result<()> {
+    let (client, remote_path) = connect(opts, alist_url).await?;
+    if !client.exists(&remote_path).await? {
+        return Err(AlistError::NotFound(remote_path));
+    }
+    if opts.json {
+        print_json(&ExistsResult {
+            status: "ok",
+            path: &remote_path,
+        })?;
+    }
+    Ok(())
+}
+
+/// The entries of the parent directory whose names match the glob pattern that ends
+/// `path`, such as `/logs/*.gz`, as paths and whether each is a directory.
+async fn expand_remote(client: &AlistClient, path: &str) -> Result<Vec<(String, bool)>> {
+    let (parent, pattern) = split_parent(path);
+    let pattern = Pattern::new(pattern)
+        .map_err(|err| AlistError::Config(format!("invalid pattern {}: {}", pattern, err)))?;
+    let matches: Vec<(String, bool)> = client
+        .list(parent)
+        .await?
+        .into_iter()
+        .filter(|entry| pattern.matches(&entry.name))
+        .map(|entry| (join(parent, &entry.name), entry.is_dir))
+        .collect();
+    if matches.is_empty() {
+        return Err(AlistError::NotFound(format!("nothing matches {}", path)));
+    }
+    Ok(matches)
+}
+
+pub async fn cmd_rm(opts: &GlobalOpts, args: &RmArgs) -> Result<()> {
+    let (client, paths) = connect_many(opts, &args.alist_urls).await?;
+
+    // Patterns in the last component are matched against a listing of the parent,
+    // and their matches only removed once confirmed as a whole.
+    let mut expanded = Vec::new();
+    let mut skipped = Vec::new();
+    for path in &paths {
+        if !is_glob(split_parent(path).1) {
+            let is_dir = client.get(path).await?.is_dir;
+            expanded.push((path.clone(), is_dir, false));
+            continue;
+        }
+        let matches = expand_remote(&client, path).await?;
+        if !args.yes && !opts.dry_run {
+            for (found, is_dir) in &matches {
+                eprintln!("  {}{}", found, if *is_dir { "/" } else { "" });
+            }
+            if !confirm(&format!("Remove these {} entries matching {}?", matches.len(), path))? {
+                eprintln!("Skipped {}", path);
+                skipped.push(path.clone());
+                continue;
+            }
+        }
+        expanded.extend(matches.into_iter().map(|(found, is_dir)| (found, is_dir, true)));
+    }
+
+    let mut targets = Vec::new();
+    for (path, is_dir, confirmed) in &expanded {
+        if *is_dir {
+            if !args.recursive {
+                return Err(AlistError::Config(format!(
+                    "{} is a directory; pass --recursive to remove it",
+                    path
+                )));
+            }
+            if !args.yes
+                && !opts.dry_run
+                && !confirmed
+                && !confirm(&format!("Remove directory {} and everything in it?", path))?
+            {
+                eprintln!("Skipped {}", path);
+                skipped.push(path.clone());
+                continue;
+            }
+        }
+        targets.push(path.as_str());
+    }
+
+    let groups = group_by_parent(targets);
+    if opts.dry_run {
+        for (dir, names) in &groups {
+            for name in names {
+                print_planned(opts, "remove", &join(dir, name), None)?;
+            }
+        }
+        return Ok(());
+    }
+
+    let mut removed = Vec::new();
+    for (dir, names) in &groups {
+        client.remove(dir, names).await?;
+        for name in names {
+            let path = join(dir, name);
+            if !opts.json {
+                println!("Removed {}", path);
+            }
+            removed.push(path);
+        }
+    }
+
+    if opts.json {
+        print_json(&RmResult {
+            status: "ok",
+            removed,
+            skipped,
+        })?;
+    }
+    Ok(())
+}
+
+pub async fn cmd_mv(opts: &GlobalOpts, args: &MvArgs) -> Result<()> {
+    let (client, mut paths) = connect_many(opts, &args.alist_urls).await?;
+    let dst_dir = paths.pop().expect("clap requires a destination");
+    let plan = plan_transfers(&client, args.on_conflict, &paths, &dst_dir).await?;
+    if opts.dry_run {
+        return print_transfers(opts, "move", &plan, &dst_dir);
+    }
+    plan.check()?;
+    let TransferPlan { planned, skipped, .. } = plan;
+    report_skipped(opts, &skipped, &dst_dir);
+
+    prepare_transfers(&client, &planned, &dst_dir).await?;
+    let staged: Vec<String> = planned.iter().map(Planned::staged).collect();
+    for (src_dir, names) in &group_by_parent(staged.iter().map(String::as_str)) {
+        client.move_files(src_dir, &dst_dir, names).await?;
+    }
+
+    let mut items = Vec::new();
+    for item in planned {
+        let item = Transfer {
+            to: join(&dst_dir, &item.name),
+            from: item.from,
+        };
+        if !opts.json {
+            println!("Moved {} -> {}", item.from, item.to);
+        }
+        items.push(item);
+    }
+
+    if opts.json {
+        print_json(&TransferResult {
+            status: "ok",
+            items,
+            skipped,
+            tasks: Vec::new(),
+        })?;
+    }
+    Ok(())
+}
+
+pub async fn cmd_rename(opts: &GlobalOpts, args: &RenameArgs) -> Result<()> {
+    let (client, path) = connect(opts, &args.alist_url).await?;
+    let item = Transfer {
+        to: join(split_parent(&path).0, &args.new_name),
+        from: path,
+    };
+    if opts.dry_run {
+        return print_planned(opts, "rename", &item.from, Some(&item.to));
+    }
+    client.rename(&item.from, &args.new_name).await?;
+
+    if opts.json {
+        print_json(&TransferResult {
+            status: "ok",
+            items: vec![item],
+            skipped: Vec::new(),
+            tasks: Vec::new(),
+        })?;
+    } else {
+        println!("Renamed {} -> {}", item.from, item.to);
+    }
+    Ok(())
+}
+
+/// Copy into a directory. Sources getting a new name are renamed for the copy and
+/// renamed back afterwards, so their copy tasks are always waited for.
+pub async fn cmd_cp(opts: &GlobalOpts, args: &CpArgs) -> Result<()> {
+    let (client, mut paths) = connect_many(opts, &args.alist_urls).await?;
+    let dst_dir = paths.pop().expect("clap requires a destination");
+    let plan = plan_transfers(&client, args.on_conflict, &paths, &dst_dir).await?;
+    if opts.dry_run {
+        return print_transfers(opts, "copy", &plan, &dst_dir);
+    }
+    plan.check()?;
+    let TransferPlan { planned, skipped, .. } = plan;
+    report_skipped(opts, &skipped, &dst_dir);
+
+    prepare_transfers(&client, &planned, &dst_dir).await?;
+    let staged: Vec<String> = planned.iter().map(Planned::staged).collect();
+    let mut tasks = Vec::new();
+    for (src_dir, names) in &group_by_parent(staged.iter().map(String::as_str)) {
+        tasks.extend(client.copy_files(src_dir, &dst_dir, names).await?);
+    }
+    let mut items = Vec::new();
+    for item in &planned {
+        let item = Transfer {
+            from: item.from.clone(),
+            to: join(&dst_dir, &item.name),
+        };
+        if !opts.json {
+            println!("Copying {} -> {}", item.from, item.to);
+        }
+        items.push(item);
+    }
+
+    let restore = planned.iter().any(Planned::renamed);
+    for task in &tasks {
+        if args.wait || restore {
+            wait_for_task(opts, &client, "copy", task).await?;
+        } else if !opts.json {
+            println!("Started task {}: {}", task.id, task.name);
+        }
+    }
+    for item in planned.iter().filter(|item| item.renamed()) {
+        client.rename(&item.staged(), split_parent(&item.from).1).await?;
+    }
+
+    if opts.json {
+        print_json(&TransferResult {
+            status: "ok",
+            items,
+            skipped,
+            tasks,
+        })?;
+    }
+    Ok(())
+}
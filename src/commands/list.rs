@@ -0,0 +1,202 @@
+use alist_cli::model::{FileEntry, ListRequest, SearchEntry, SearchRequest};
+use alist_cli::path::{join, strip_prefix};
+use alist_cli::Result;
+use serde::Serialize;
+use std::io::IsTerminal;
+
+use super::{connect, print_json, select_fields};
+use crate::cli::{FormatPiece, GlobalOpts, ListArgs, ListField, ListFormat, OutputFormat, SearchArgs};
+
+pub async fn cmd_list(opts: &GlobalOpts, args: &ListArgs) -> Result<()> {
+    let (client, remote_dir) = connect(opts, &args.alist_url).await?;
+    let request = ListRequest {
+        page: args.page,
+        per_page: args.per_page.unwrap_or(if args.all { 100 } else { 0 }),
+        refresh: args.refresh,
+        ..ListRequest::new(remote_dir)
+    };
+    let entries = if args.all || request.per_page == 0 {
+        client.list_all(&request).await?
+    } else {
+        let data = client.list_page(&request).await?;
+        let entries = data.content.unwrap_or_default();
+        let shown = request.page.saturating_sub(1) as u64 * request.per_page as u64 + entries.len() as u64;
+        if data.total > shown {
+            eprintln!(
+                "Showing page {} ({} of {} entries); use --page for more or --all for everything",
+                request.page,
+                entries.len(),
+                data.total
+            );
+        }
+        entries
+    };
+
+    if !args.select_fields.is_empty() {
+        let entries = serde_json::to_value(&entries)?;
+        let selected = select_fields(&entries, &args.select_fields, args.strict_select)?;
+        println!("{}", serde_json::to_string_pretty(&selected)?);
+    } else if let Some(output) = args.output {
+        print_entries(output, &request.path, &entries, args.bytes)?;
+    } else if args.json || opts.json {
+        print_entries(OutputFormat::Json, &request.path, &entries, args.bytes)?;
+    } else if let Some(format) = &args.format {
+        for entry in &entries {
+            println!("{}", render(format, entry, &request.path));
+        }
+    } else if args.long {
+        print_long(&entries, args.bytes, use_color());
+    } else {
+        let color = use_color();
+        for entry in &entries {
+            println!("{}", colored_name(entry, color));
+        }
+    }
+    Ok(())
+}
+
+/// `--json` result of `search`.
+#[derive(Serialize)]
+struct SearchResult<'a> {
+    status: &'static str,
+    total: u64,
+    items: &'a [SearchEntry],
+}
+
+pub async fn cmd_search(opts: &GlobalOpts, args: &SearchArgs) -> Result<()> {
+    let (client, parent) = connect(opts, &args.parent).await?;
+    let data = client
+        .search(&SearchRequest {
+            parent: parent.clone(),
+            keywords: args.keyword.clone(),
+            scope: args.scope as u8,
+            page: args.page,
+            per_page: args.per_page,
+            password: String::new(),
+        })
+        .await?;
+    let items = data.content.unwrap_or_default();
+
+    let output = args.output.unwrap_or(if opts.json { OutputFormat::Json } else { OutputFormat::Plain });
+    if output == OutputFormat::Json {
+        return print_json(&SearchResult {
+            status: "ok",
+            total: data.total,
+            items: &items,
+        });
+    }
+    // Named by their path below the directory searched, so that the table shows
+    // where each match is.
+    let entries: Vec<FileEntry> = items
+        .iter()
+        .map(|item| {
+            let path = join(&item.parent, &item.name);
+            FileEntry {
+                name: strip_prefix(&path, &parent).unwrap_or(&path).to_string(),
+                size: item.size,
+                is_dir: item.is_dir,
+                modified: String::new(),
+                hash_info: None,
+            }
+        })
+        .collect();
+    print_entries(output, &parent, &entries, args.bytes)?;
+    if data.total > items.len() as u64 {
+        eprintln!(
+            "Showing {} of {} matches; use --page for more",
+            items.len(),
+            data.total
+        );
+    }
+    Ok(())
+}
+
+fn display_name(entry: &FileEntry) -> String {
+    if entry.is_dir {
+        format!("{}/", entry.name)
+    } else {
+        entry.name.clone()
+    }
+}
+
+/// Print `entries` of the remote directory `dir` in the `--output` format.
+fn print_entries(output: OutputFormat, dir: &str, entries: &[FileEntry], bytes: bool) -> Result<()> {
+    match output {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(entries)?),
+        OutputFormat::Plain => {
+            for entry in entries {
+                println!("{}", join(dir, &display_name(entry)));
+            }
+        }
+        OutputFormat::Table => print_long(entries, bytes, use_color()),
+    }
+    Ok(())
+}
+
+/// Fill in an `ls --format` template for `entry`, found in the remote directory `dir`.
+fn render(format: &ListFormat, entry: &FileEntry, dir: &str) -> String {
+    let mut line = String::new();
+    for piece in &format.0 {
+        match piece {
+            FormatPiece::Text(text) => line.push_str(text),
+            FormatPiece::Field(ListField::Name) => line.push_str(&entry.name),
+            FormatPiece::Field(ListField::Path) => line.push_str(&join(dir, &entry.name)),
+            FormatPiece::Field(ListField::Size) => line.push_str(&entry.size.to_string()),
+            FormatPiece::Field(ListField::Modified) => line.push_str(&entry.modified),
+            FormatPiece::Field(ListField::Type) => line.push_str(if entry.is_dir { "dir" } else { "file" }),
+        }
+    }
+    line
+}
+
+/// Whether to colour names: only on a terminal, and not if `NO_COLOR` is set.
+fn use_color() -> bool {
+    std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+}
+
+/// The display name, with directories in bold blue if `color` is set.
+fn colored_name(entry: &FileEntry, color: bool) -> String {
+    if color && entry.is_dir {
+        format!("\x1b[1;34m{}\x1b[0m", display_name(entry))
+    } else {
+        display_name(entry)
+    }
+}
+
+/// Format a size with binary suffixes, such as `512 B` or `1.5 MiB`.
+fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Shorten an RFC 3339 timestamp such as `2024-01-02T03:04:05.678+08:00` to `2024-01-02 03:04:05`.
+fn format_time(modified: &str) -> String {
+    modified.chars().take(19).collect::<String>().replacen('T', " ", 1)
+}
+
+fn print_long(entries: &[FileEntry], bytes: bool, color: bool) {
+    let sizes: Vec<String> = entries
+        .iter()
+        .map(|entry| if bytes { entry.size.to_string() } else { human_bytes(entry.size) })
+        .collect();
+    let width = sizes.iter().map(String::len).max().unwrap_or(0);
+    for (entry, size) in entries.iter().zip(&sizes) {
+        println!(
+            "{} {:>width$} {} {}",
+            if entry.is_dir { 'd' } else { '-' },
+            size,
+            format_time(&entry.modified),
+            colored_name(entry, color),
+            width = width,
+        );
+    }
+}
@@ -0,0 +1,542 @@
+//! Handlers for each subcommand, plus the connection setup they share.
+
+use alist_cli::client::{normalize_base, split_url, split_url_under};
+use alist_cli::credentials;
+use alist_cli::crypt::Passphrase;
+use alist_cli::model::TaskInfo;
+use alist_cli::path::{resolve, split_parent, under};
+use alist_cli::progress::{json_bar, task_bar, transfer_bar, ProgressFormat, TotalProgress};
+use alist_cli::retry::RetryPolicy;
+use alist_cli::token_cache::TokenCache;
+use alist_cli::{AlistClient, AlistError, Result};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget};
+use serde::Serialize;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+use url::Url;
+
+use crate::cli::GlobalOpts;
+
+pub mod api;
+pub mod audit;
+pub mod auth;
+pub mod batch;
+pub mod doctor;
+pub mod download;
+pub mod filter;
+pub mod fs;
+pub mod list;
+pub mod offline;
+pub mod profiles;
+pub mod storage;
+pub mod sync;
+pub mod tasks;
+pub mod upload;
+pub mod verify;
+
+/// Resolve the account password from `--password`, stdin or an interactive prompt.
+fn read_password(password: Option<&str>, username: &str) -> Result<String> {
+    match password {
+        Some("-") => {
+            let mut line = String::new();
+            io::stdin().read_line(&mut line)?;
+            Ok(line.trim_end_matches(['\r', '\n']).to_string())
+        }
+        Some(password) => Ok(password.to_string()),
+        None => Ok(rpassword::prompt_password(format!("Password for {}: ", username))?),
+    }
+}
+
+/// The password from [`read_password`], read only the first time it is needed, so a
+/// login after the token expired does not read `--password -` from an exhausted stdin.
+fn read_password_once(known: &OnceLock<String>, password: Option<&str>, username: &str) -> Result<String> {
+    if let Some(password) = known.get() {
+        return Ok(password.clone());
+    }
+    let password = read_password(password, username)?;
+    Ok(known.get_or_init(|| password).clone())
+}
+
+/// The passphrase for `--encrypt` and `--decrypt`: `ALIST_PASSPHRASE`, or else asked
+/// for, twice with `confirm` so a typo cannot lock the files away.
+pub fn read_passphrase(confirm: bool) -> Result<Passphrase> {
+    let passphrase = match std::env::var("ALIST_PASSPHRASE") {
+        Ok(passphrase) => passphrase,
+        Err(_) => {
+            let passphrase = rpassword::prompt_password("Encryption passphrase: ")?;
+            if confirm && rpassword::prompt_password("Repeat the passphrase: ")? != passphrase {
+                return Err(AlistError::Config("the passphrases do not match".to_string()));
+            }
+            passphrase
+        }
+    };
+    if passphrase.is_empty() {
+        return Err(AlistError::Config("the encryption passphrase is empty".to_string()));
+    }
+    Ok(Passphrase::new(passphrase))
+}
+
+/// The password to log in to `base_url` as `username` with, if known without asking:
+/// `--password`, or with `--use-keyring` the one stored in the system keyring.
+fn stored_password(opts: &GlobalOpts, base_url: &str, username: &str) -> Option<String> {
+    if opts.password.is_some() || !opts.use_keyring {
+        return opts.password.clone();
+    }
+    let password = credentials::load_password(base_url, username);
+    if password.is_none() {
+        debug!("No password for {} on {} in the system keyring", username, base_url);
+    }
+    password
+}
+
+/// The password to log in to `base_url` as `username` with, asked for if not known.
+pub fn account_password(opts: &GlobalOpts, base_url: &str, username: &str) -> Result<String> {
+    read_password(stored_password(opts, base_url, username).as_deref(), username)
+}
+
+/// Ask a yes/no question on stderr, defaulting to no.
+fn confirm(prompt: &str) -> Result<bool> {
+    eprint!("{} [y/N] ", prompt);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes" | "Yes"))
+}
+
+/// Split a remote argument into the server base URL and remote path. Full URLs
+/// name their own server; plain paths are on `--server` or the profile's server, at
+/// the path [`plain_path`] resolves them to.
+///
+/// A full URL is taken to point at a server hosted at the root of its host, unless
+/// it lies below `--server`, which may include a path prefix such as `/alist`.
+pub fn split_remote(opts: &GlobalOpts, remote: &str) -> Result<(String, String)> {
+    let server = opts.server.as_deref().map(normalize_base).transpose()?;
+    if remote.starts_with("http://") || remote.starts_with("https://") {
+        if let Some(server) = &server {
+            if let Some(path) = split_url_under(server, remote) {
+                return Ok((server.clone(), path));
+            }
+        }
+        return split_url(remote);
+    }
+    let Some(server) = server else {
+        return Err(AlistError::InvalidUrl(format!(
+            "{} (give a full URL, or --server for plain paths)",
+            remote
+        )));
+    };
+    Ok((server, plain_path(opts, remote)))
+}
+
+/// The path on the server a plain remote path refers to: resolved against `--cwd` or
+/// the profile's `default_dir` unless it starts with `/`, and below `--base-path`.
+pub fn plain_path(opts: &GlobalOpts, remote: &str) -> String {
+    let path = resolve(opts.cwd.as_deref().unwrap_or("/"), remote);
+    under(opts.base_path.as_deref().unwrap_or("/"), &path)
+}
+
+/// Build a client for `base_url` from the connection options, without logging in.
+pub fn build_client(opts: &GlobalOpts, base_url: String) -> Result<AlistClient> {
+    if opts.insecure {
+        warn!("TLS certificate verification is disabled (--insecure)");
+    } else if opts.no_verify_tls_hostname {
+        warn!(
+            "TLS hostname verification is disabled (--no-verify-tls-hostname); certificates are still \
+             checked against the trusted roots"
+        );
+    }
+    if let Some(name) = &opts.tls_server_name {
+        warn!(
+            "TLS certificates are checked against {} instead of the server's host (--tls-server-name)",
+            name
+        );
+    }
+    let mut client = AlistClient::with_options(base_url, &opts.client_options())?
+        .with_dir_password(opts.dir_password.clone().unwrap_or_default())
+        .with_dir_password_prompt(|| Ok(rpassword::prompt_password("Folder password: ")?))
+        .with_retry(RetryPolicy {
+            max_retry_after: Duration::from_secs(opts.max_retry_after),
+            retry_codes: opts.retry_on_code.clone(),
+            force: opts.force_retry,
+            ..RetryPolicy::with_retries(opts.retries)
+        })
+        .with_rate_limit(opts.limit_rate)
+        .with_chunk_size(opts.chunk_size)
+        .with_trace_body(opts.trace_body);
+    if let Some(scheme) = &opts.auth_scheme {
+        client = client.with_auth_scheme(scheme);
+    }
+    if let Some(name) = &opts.token_header {
+        client = client.with_token_header(name.clone());
+    }
+    Ok(client)
+}
+
+/// Log in to the server that `alist_url` points at, returning the client and remote path.
+///
+/// Should the token stop working mid-session, the client logs in again by itself and
+/// caches the new token. A token given with `--token` or `ALIST_TOKEN` is used as it
+/// is, without logging in, and with `--anonymous` there is neither login nor token.
+pub async fn connect(opts: &GlobalOpts, alist_url: &str) -> Result<(AlistClient, String)> {
+    let (base_url, remote_path) = split_remote(opts, alist_url)?;
+    if opts.time {
+        time_connection(&base_url).await;
+    }
+    if opts.anonymous {
+        return Ok((build_client(opts, base_url)?.with_anonymous(), remote_path));
+    }
+    if let Some(token) = &opts.token {
+        let mut client = build_client(opts, base_url)?;
+        client.set_token(token.as_str());
+        return Ok((client, remote_path));
+    }
+    let username = opts.username()?;
+    let password = stored_password(opts, &base_url, username);
+    let known_password = Arc::new(OnceLock::new());
+    let prompt_name = username.to_string();
+    let cache_token = (!opts.no_cache).then(|| (base_url.clone(), username.to_string()));
+    let mut client = build_client(opts, base_url)?
+        .with_relogin(
+            username,
+            {
+                let (known_password, password) = (known_password.clone(), password.clone());
+                move || read_password_once(&known_password, password.as_deref(), &prompt_name)
+            },
+            move |token| {
+                if let Some((base_url, username)) = &cache_token {
+                    let mut cache = TokenCache::load();
+                    cache.insert(base_url, username, token);
+                    if let Err(err) = cache.save() {
+                        warn!("Could not cache the new token: {}", err);
+                    }
+                }
+            },
+        )
+        .with_otp_prompt(|| Ok(rpassword::prompt_password("Two-factor code: ")?));
+
+    if opts.no_cache {
+        let password = read_password_once(&known_password, password.as_deref(), username)?;
+        let started = Instant::now();
+        client.login_with_otp(username, &password, opts.otp.as_deref()).await?;
+        TIMINGS.lock().expect("timings lock").login = Some(started.elapsed());
+        return Ok((client, remote_path));
+    }
+
+    let mut cache = TokenCache::load();
+    match cache.get(client.base_url(), username) {
+        Some(token) => client.set_token(token),
+        None => {
+            let password = read_password_once(&known_password, password.as_deref(), username)?;
+            let started = Instant::now();
+            client.login_with_otp(username, &password, opts.otp.as_deref()).await?;
+            TIMINGS.lock().expect("timings lock").login = Some(started.elapsed());
+            if let Some(token) = client.token() {
+                cache.insert(client.base_url(), username, &token);
+                cache.save()?;
+            }
+        }
+    }
+    Ok((client, remote_path))
+}
+
+/// Set up a client that uploads over WebDAV with HTTP Basic auth, without logging in.
+pub fn connect_webdav(opts: &GlobalOpts, alist_url: &str) -> Result<(AlistClient, String)> {
+    let (base_url, remote_path) = split_remote(opts, alist_url)?;
+    let username = opts.username()?;
+    let password = account_password(opts, &base_url, username)?;
+    Ok((build_client(opts, base_url)?.with_webdav(username, password), remote_path))
+}
+
+/// Like [`connect`], for commands taking several remote arguments, which must all
+/// be on the same server.
+pub async fn connect_many(opts: &GlobalOpts, alist_urls: &[String]) -> Result<(AlistClient, Vec<String>)> {
+    let (first, rest) = split_first(alist_urls)?;
+    let (client, first_path) = connect(opts, first).await?;
+    let paths = same_server(opts, &client, first, first_path, rest)?;
+    Ok((client, paths))
+}
+
+/// Like [`connect_webdav`], for several remote arguments on the same server.
+pub fn connect_webdav_many(opts: &GlobalOpts, alist_urls: &[String]) -> Result<(AlistClient, Vec<String>)> {
+    let (first, rest) = split_first(alist_urls)?;
+    let (client, first_path) = connect_webdav(opts, first)?;
+    let paths = same_server(opts, &client, first, first_path, rest)?;
+    Ok((client, paths))
+}
+
+fn split_first(alist_urls: &[String]) -> Result<(&String, &[String])> {
+    alist_urls
+        .split_first()
+        .ok_or_else(|| AlistError::Config("no remote paths given".to_string()))
+}
+
+/// The remote paths of `first` and `rest`, checking that they are all on `client`'s server.
+fn same_server(
+    opts: &GlobalOpts,
+    client: &AlistClient,
+    first: &str,
+    first_path: String,
+    rest: &[String],
+) -> Result<Vec<String>> {
+    let mut paths = vec![first_path];
+    for alist_url in rest {
+        let (base_url, path) = split_remote(opts, alist_url)?;
+        if base_url != client.base_url() {
+            return Err(AlistError::Config(format!(
+                "{} is not on the same server as {}",
+                alist_url, first
+            )));
+        }
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Print one `--json` result object as a line on stdout.
+pub fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string(value)?);
+    Ok(())
+}
+
+/// Keep only `fields` of a JSON result, or of each element of an array, as an object
+/// keyed by each field as given. A field is a JSON pointer such as `/data/total`, or
+/// the same with dots, as in `data.total`. A field that is missing is null, or with
+/// `strict` an error.
+pub fn select_fields(
+    value: &serde_json::Value,
+    fields: &[String],
+    strict: bool,
+) -> Result<serde_json::Value> {
+    if let serde_json::Value::Array(items) = value {
+        return items
+            .iter()
+            .map(|item| select_fields(item, fields, strict))
+            .collect::<Result<Vec<_>>>()
+            .map(serde_json::Value::Array);
+    }
+    let mut selected = serde_json::Map::new();
+    for field in fields {
+        let pointer = match field.strip_prefix('/') {
+            Some(_) => field.clone(),
+            None => format!("/{}", field.replace('.', "/")),
+        };
+        let found = value.pointer(&pointer);
+        if found.is_none() && strict {
+            return Err(AlistError::Config(format!("`{}` is not a field of the result", field)));
+        }
+        selected.insert(field.clone(), found.cloned().unwrap_or_default());
+    }
+    Ok(serde_json::Value::Object(selected))
+}
+
+/// One step `--dry-run` would have taken, in `--json` mode.
+#[derive(Serialize)]
+struct PlannedAction<'a> {
+    status: &'static str,
+    action: &'a str,
+    path: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    to: Option<&'a str>,
+}
+
+/// Report that `--dry-run` skipped `action` on `path` (and `to`).
+pub fn print_planned(opts: &GlobalOpts, action: &str, path: &str, to: Option<&str>) -> Result<()> {
+    if opts.json {
+        return print_json(&PlannedAction {
+            status: "dry-run",
+            action,
+            path,
+            to,
+        });
+    }
+    match to {
+        Some(to) => println!("Would {} {} -> {}", action, path, to),
+        None => println!("Would {} {}", action, path),
+    }
+    Ok(())
+}
+
+/// The progress bar for transferring `name`, of `size` bytes if known, in the form
+/// `--no-progress` and `--progress-format` ask for.
+pub fn transfer_progress(opts: &GlobalOpts, name: &str, size: Option<u64>) -> ProgressBar {
+    match (opts.no_progress, opts.progress_format) {
+        (true, _) => ProgressBar::hidden(),
+        (false, ProgressFormat::Json) => json_bar(name, size),
+        (false, ProgressFormat::Bar) => {
+            let bar = transfer_bar(size);
+            if let Some(hz) = redraw_hz(opts) {
+                bar.set_draw_target(ProgressDrawTarget::stderr_with_hz(hz));
+            }
+            bar
+        }
+    }
+}
+
+/// How often a second bars are redrawn with `--progress-refresh`.
+fn redraw_hz(opts: &GlobalOpts) -> Option<u8> {
+    opts.progress_refresh.map(|ms| (1000 / ms.max(1)).clamp(1, u8::MAX as u64) as u8)
+}
+
+/// How long to wait between bringing a total bar up to date.
+pub fn progress_refresh(opts: &GlobalOpts) -> Duration {
+    Duration::from_millis(opts.progress_refresh.unwrap_or(50).max(1))
+}
+
+/// A group of bars drawn on stderr, redrawn as often as `--progress-refresh` says.
+pub fn multi_progress(opts: &GlobalOpts) -> MultiProgress {
+    match redraw_hz(opts) {
+        Some(hz) => MultiProgress::with_draw_target(ProgressDrawTarget::stderr_with_hz(hz)),
+        None => MultiProgress::new(),
+    }
+}
+
+/// The total bar for `files` transfers at once, or `None` if progress is not drawn as
+/// bars.
+pub fn total_progress(opts: &GlobalOpts, bars: &MultiProgress, files: usize) -> Option<TotalProgress> {
+    (!opts.no_progress && opts.progress_format == ProgressFormat::Bar)
+        .then(|| TotalProgress::new(bars, files, opts.per_file_progress))
+}
+
+/// Wait for a background task of the given kind to finish, showing its progress,
+/// for at most `--task-timeout` seconds.
+pub async fn wait_for_task(opts: &GlobalOpts, client: &AlistClient, kind: &str, task: &TaskInfo) -> Result<()> {
+    let bar = if opts.no_progress || opts.progress_format == ProgressFormat::Json {
+        ProgressBar::hidden()
+    } else {
+        task_bar(&task.name)
+    };
+    let timeout = opts.task_timeout.map(Duration::from_secs);
+    let result = client
+        .wait_for_task(kind, &task.id, timeout, |info| {
+            bar.set_position(info.progress.clamp(0.0, 100.0) as u64);
+            bar.set_message(info.state_name());
+        })
+        .await;
+    match result {
+        Ok(_) => {
+            bar.finish();
+            Ok(())
+        }
+        Err(err) => {
+            bar.abandon();
+            Err(err)
+        }
+    }
+}
+
+/// Remote files that only exist while a command runs, such as the temporary files of
+/// `--atomic` uploads, and the clients to remove them with should it be interrupted.
+static TEMPORARY: Mutex<Vec<(Arc<AlistClient>, String)>> = Mutex::new(Vec::new());
+
+/// Note that `path` is to be removed if the command is interrupted.
+pub fn track_temporary(client: &Arc<AlistClient>, path: &str) {
+    TEMPORARY
+        .lock()
+        .expect("temporary files lock")
+        .push((client.clone(), path.to_string()));
+}
+
+/// Forget about `path` once it has been renamed or removed.
+pub fn untrack_temporary(path: &str) {
+    TEMPORARY
+        .lock()
+        .expect("temporary files lock")
+        .retain(|(_, temporary)| temporary != path);
+}
+
+/// Remove the temporary files of an interrupted command, giving up after a few seconds.
+pub async fn remove_temporary() {
+    let temporary = std::mem::take(&mut *TEMPORARY.lock().expect("temporary files lock"));
+    let removals = temporary.iter().map(|(client, path)| async move {
+        let (parent, name) = split_parent(path);
+        if let Err(err) = client.remove(parent, &[name.to_string()]).await {
+            warn!("Could not remove the partial upload {}: {}", path, err);
+        }
+    });
+    let all = futures_util::future::join_all(removals);
+    if tokio::time::timeout(Duration::from_secs(5), all).await.is_err() {
+        warn!("Gave up removing partial uploads");
+    }
+}
+
+/// How long the parts of a command took, for `--time`.
+#[derive(Default)]
+struct Timings {
+    resolve: Option<Duration>,
+    connect: Option<Duration>,
+    login: Option<Duration>,
+    /// Time spent uploading, summed over all files, and the bytes sent meanwhile.
+    upload: Duration,
+    uploaded: u64,
+    uploads: usize,
+}
+
+static TIMINGS: Mutex<Timings> = Mutex::new(Timings {
+    resolve: None,
+    connect: None,
+    login: None,
+    upload: Duration::ZERO,
+    uploaded: 0,
+    uploads: 0,
+});
+
+/// Time resolving the host of `base_url` and opening a TCP connection to it.
+async fn time_connection(base_url: &str) {
+    let Some((host, port)) = Url::parse(base_url)
+        .ok()
+        .and_then(|url| Some((url.host_str()?.to_string(), url.port_or_known_default()?)))
+    else {
+        return;
+    };
+    let started = Instant::now();
+    let addr = match tokio::net::lookup_host((host.as_str(), port)).await {
+        Ok(mut addrs) => addrs.next(),
+        Err(err) => {
+            warn!("Could not resolve {} to time the connection: {}", host, err);
+            return;
+        }
+    };
+    let resolved = Instant::now();
+    TIMINGS.lock().expect("timings lock").resolve = Some(resolved - started);
+    let Some(addr) = addr else {
+        return;
+    };
+    match tokio::net::TcpStream::connect(addr).await {
+        Ok(_) => TIMINGS.lock().expect("timings lock").connect = Some(resolved.elapsed()),
+        Err(err) => warn!("Could not connect to {} to time the connection: {}", addr, err),
+    }
+}
+
+/// Note that uploading `bytes` took `elapsed`, for `--time`.
+pub fn record_upload(bytes: u64, elapsed: Duration) {
+    let mut timings = TIMINGS.lock().expect("timings lock");
+    timings.upload += elapsed;
+    timings.uploaded += bytes;
+    timings.uploads += 1;
+}
+
+/// Print what `--time` collected to stderr.
+pub fn print_timings() {
+    let timings = TIMINGS.lock().expect("timings lock");
+    let show = |name: &str, duration: Option<Duration>| match duration {
+        Some(duration) => eprintln!("{:<10} {:.3}s", name, duration.as_secs_f64()),
+        None => eprintln!("{:<10} -", name),
+    };
+    show("Resolve:", timings.resolve);
+    show("Connect:", timings.connect);
+    show("Login:", timings.login);
+    if timings.uploads > 0 {
+        let secs = timings.upload.as_secs_f64();
+        let speed = if secs > 0.0 { timings.uploaded as f64 / secs } else { 0.0 };
+        eprintln!(
+            "{:<10} {:.3}s for {} bytes in {} files, {:.1} KiB/s",
+            "Upload:",
+            secs,
+            timings.uploaded,
+            timings.uploads,
+            speed / 1024.0
+        );
+    }
+}
@@ -0,0 +1,45 @@
+use alist_cli::model::TaskInfo;
+use alist_cli::Result;
+use serde::Serialize;
+
+use super::{connect, print_json, print_planned, wait_for_task};
+use crate::cli::{GlobalOpts, OfflineDownloadArgs};
+
+/// `--json` result of `offline-download`.
+#[derive(Serialize)]
+struct OfflineResult<'a> {
+    status: &'static str,
+    path: &'a str,
+    tasks: Vec<TaskInfo>,
+}
+
+pub async fn cmd_offline_download(opts: &GlobalOpts, args: &OfflineDownloadArgs) -> Result<()> {
+    let (client, remote_dir) = connect(opts, &args.alist_url).await?;
+    if opts.dry_run {
+        for url in &args.urls {
+            print_planned(opts, "fetch", url, Some(&remote_dir))?;
+        }
+        return Ok(());
+    }
+
+    let tasks = client.add_offline_download(&remote_dir, &args.urls, args.tool).await?;
+    for task in &tasks {
+        if !opts.json {
+            println!("Started task {}: {}", task.id, task.name);
+        }
+    }
+    if args.wait {
+        for task in &tasks {
+            wait_for_task(opts, &client, "offline_download", task).await?;
+        }
+    }
+
+    if opts.json {
+        print_json(&OfflineResult {
+            status: if args.wait { "ok" } else { "started" },
+            path: &remote_dir,
+            tasks,
+        })?;
+    }
+    Ok(())
+}
@@ -0,0 +1,107 @@
+//! Running `info` or `me` against the servers of several profiles at once.
+
+use alist_cli::{AlistError, Result};
+use serde::Serialize;
+
+use super::{build_client, connect, print_json, split_remote};
+use crate::cli::{Command, GlobalOpts};
+
+/// `--json` result of one profile of an `--all-profiles` or `--profiles` run.
+#[derive(Serialize)]
+struct ProfileResult<'a> {
+    status: &'static str,
+    profile: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    server: Option<&'a str>,
+    /// The server's version for `info`, or the username and role for `me`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Run `command` against the server of each profile in turn, carrying on past
+/// failures, and print a table of the results.
+pub async fn cmd_each_profile(opts: &GlobalOpts, profiles: &[String], command: &Command) -> Result<()> {
+    let column = match command {
+        Command::Info { alist_url: None } => "VERSION",
+        Command::Me { alist_url: None } => "USER",
+        Command::Info { .. } | Command::Me { .. } => {
+            return Err(AlistError::Config(
+                "--all-profiles and --profiles use each profile's server; give no URL".to_string(),
+            ))
+        }
+        _ => {
+            return Err(AlistError::Config(
+                "--all-profiles and --profiles only work with `info` and `me`".to_string(),
+            ))
+        }
+    };
+
+    let mut rows = Vec::new();
+    for name in profiles {
+        let (server, result) = match opts.for_profile(name) {
+            Ok(opts) => (opts.server.clone(), run_one(&opts, command).await),
+            Err(err) => (None, Err(err)),
+        };
+        rows.push((name.as_str(), server, result));
+    }
+    let failed = rows.iter().filter(|(_, _, result)| result.is_err()).count();
+
+    if opts.json {
+        for (name, server, result) in &rows {
+            print_json(&ProfileResult {
+                status: if result.is_ok() { "ok" } else { "failed" },
+                profile: name,
+                server: server.as_deref(),
+                result: result.as_deref().ok(),
+                error: result.as_ref().err().map(ToString::to_string),
+            })?;
+        }
+    } else {
+        let width = rows.iter().map(|(name, _, _)| name.len()).max().unwrap_or(0).max(7);
+        let server_width = rows
+            .iter()
+            .map(|(_, server, _)| server.as_deref().map_or(1, str::len))
+            .max()
+            .unwrap_or(0)
+            .max(6);
+        println!("{:<width$}  {:<server_width$}  {}", "PROFILE", "SERVER", column);
+        for (name, server, result) in &rows {
+            let result = match result {
+                Ok(result) => result.clone(),
+                Err(err) => format!("error: {}", err),
+            };
+            println!(
+                "{:<width$}  {:<server_width$}  {}",
+                name,
+                server.as_deref().unwrap_or("-"),
+                result
+            );
+        }
+        if failed > 0 {
+            eprintln!("Failed for {} of {} profiles", failed, rows.len());
+        }
+    }
+
+    if failed == 0 {
+        Ok(())
+    } else {
+        Err(AlistError::PartialFailure {
+            failed,
+            total: rows.len(),
+        })
+    }
+}
+
+/// The server's version for `info`, or the username and role for `me`.
+async fn run_one(opts: &GlobalOpts, command: &Command) -> Result<String> {
+    if let Command::Me { .. } = command {
+        let (client, _) = connect(opts, "/").await?;
+        let user = client.me().await?;
+        return Ok(format!("{} ({})", user.username, user.role_name()));
+    }
+    let (base_url, _) = split_remote(opts, "/")?;
+    let settings = build_client(opts, base_url)?.public_settings().await?;
+    Ok(settings.version)
+}
@@ -0,0 +1,94 @@
+use alist_cli::model::Storage;
+use alist_cli::{AlistError, Result};
+use serde::Serialize;
+
+use super::{connect, print_json, print_planned};
+use crate::cli::{GlobalOpts, StorageCommand, StorageIdArgs};
+
+/// `--json` result of `storage enable` and `storage disable`.
+#[derive(Serialize)]
+struct StorageActionResult<'a> {
+    status: &'static str,
+    id: u64,
+    mount_path: &'a str,
+    disabled: bool,
+}
+
+/// `--json` result of `storage list`.
+#[derive(Serialize)]
+struct StoragesResult {
+    status: &'static str,
+    storages: Vec<Storage>,
+}
+
+pub async fn cmd_storage(opts: &GlobalOpts, command: &StorageCommand) -> Result<()> {
+    match command {
+        StorageCommand::List { alist_url } => cmd_list(opts, alist_url.as_deref()).await,
+        StorageCommand::Enable(args) => cmd_set_enabled(opts, args, true).await,
+        StorageCommand::Disable(args) => cmd_set_enabled(opts, args, false).await,
+    }
+}
+
+async fn cmd_list(opts: &GlobalOpts, alist_url: Option<&str>) -> Result<()> {
+    let (client, _) = connect(opts, alist_url.unwrap_or("/")).await?;
+    let mut storages = client.storages().await?;
+    storages.sort_by_key(|storage| storage.order);
+
+    if opts.json {
+        return print_json(&StoragesResult { status: "ok", storages });
+    }
+    if storages.is_empty() {
+        println!("No storages");
+        return Ok(());
+    }
+    println!("{:<5} {:<16} {:<8} {:<10} MOUNT PATH", "ID", "DRIVER", "ENABLED", "STATUS");
+    for storage in &storages {
+        println!(
+            "{:<5} {:<16} {:<8} {:<10} {}",
+            storage.id,
+            storage.driver,
+            if storage.disabled { "no" } else { "yes" },
+            storage.status,
+            storage.mount_path
+        );
+    }
+    Ok(())
+}
+
+/// Enable or disable a storage, then look it up again to confirm that it took.
+async fn cmd_set_enabled(opts: &GlobalOpts, args: &StorageIdArgs, enabled: bool) -> Result<()> {
+    let action = if enabled { "enable" } else { "disable" };
+    let (client, _) = connect(opts, args.alist_url.as_deref().unwrap_or("/")).await?;
+    if opts.dry_run {
+        return print_planned(opts, &format!("{} storage", action), &args.id.to_string(), None);
+    }
+
+    client.set_storage_enabled(args.id, enabled).await?;
+    let storage = client.storage(args.id).await?;
+    if storage.disabled == enabled {
+        return Err(AlistError::Api {
+            code: 500,
+            message: format!(
+                "the server accepted the request to {} storage {}, but it is still {}",
+                action,
+                args.id,
+                if storage.disabled { "disabled" } else { "enabled" }
+            ),
+        });
+    }
+    if opts.json {
+        return print_json(&StorageActionResult {
+            status: "ok",
+            id: storage.id,
+            mount_path: &storage.mount_path,
+            disabled: storage.disabled,
+        });
+    }
+    println!(
+        "Storage {} ({}) is now {}",
+        storage.id,
+        storage.mount_path,
+        if storage.disabled { "disabled" } else { "enabled" }
+    );
+    Ok(())
+}
@@ -0,0 +1,322 @@
+use alist_cli::checkpoint::Checkpoint;
+use alist_cli::client::PutOptions;
+use alist_cli::hash::{HashInfo, HashKind};
+use alist_cli::hash_cache::HashCache;
+use alist_cli::model::FileEntry;
+use alist_cli::path::{join, join_relative, split_parent};
+use alist_cli::{AlistClient, AlistError, Result};
+use chrono::DateTime;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tracing::warn;
+
+use super::{connect, print_json, print_planned, transfer_progress};
+use super::filter::{walk, Filter};
+use super::fs::group_by_parent;
+use super::upload::local_metadata;
+use crate::cli::{GlobalOpts, OnError, SyncArgs};
+
+/// `--json` result of `sync`.
+#[derive(Serialize)]
+struct SyncResult {
+    status: &'static str,
+    uploaded: Vec<String>,
+    unchanged: usize,
+    deleted: Vec<String>,
+    /// Remote paths that could not be uploaded or deleted.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    failed: Vec<String>,
+}
+
+/// Upload one changed file to `target`.
+async fn upload(opts: &GlobalOpts, client: &AlistClient, local_path: &Path, target: &str) -> Result<()> {
+    let size = tokio::fs::metadata(local_path).await?.len();
+    let progress = transfer_progress(opts, target, Some(size));
+    client
+        .put_file(local_path, target, &PutOptions::default(), &progress)
+        .await?;
+    Ok(())
+}
+
+/// Every file and directory below a remote directory, keyed by full remote path.
+#[derive(Default)]
+pub(super) struct RemoteTree {
+    pub dirs: HashSet<String>,
+    pub files: HashMap<String, FileEntry>,
+}
+
+/// What a sync is going to change on the server.
+#[derive(Default)]
+struct SyncPlan {
+    /// Remote directories to create, parents first.
+    mkdirs: Vec<String>,
+    /// Local files and the remote paths to upload them to.
+    uploads: Vec<(PathBuf, String)>,
+    unchanged: usize,
+    /// Remote paths to remove.
+    deletes: Vec<String>,
+}
+
+pub async fn cmd_sync(opts: &GlobalOpts, args: &SyncArgs) -> Result<()> {
+    // Walking a file would find nothing, and --delete would then empty the remote side.
+    if !local_metadata(&args.local_dir).await?.is_dir() {
+        return Err(AlistError::Config(format!(
+            "{} is not a directory; use `upload` for a single file",
+            args.local_dir.display()
+        )));
+    }
+    let (client, remote_dir) = connect(opts, &args.alist_url).await?;
+    let plan = plan(&client, args, &remote_dir).await?;
+
+    if opts.dry_run {
+        for dir in &plan.mkdirs {
+            print_planned(opts, "mkdir", dir, None)?;
+        }
+        for (local_path, target) in &plan.uploads {
+            print_planned(opts, "upload", &local_path.to_string_lossy(), Some(target))?;
+        }
+        for path in &plan.deletes {
+            print_planned(opts, "remove", path, None)?;
+        }
+        return Ok(());
+    }
+
+    for dir in &plan.mkdirs {
+        client.ensure_dir(dir).await?;
+    }
+
+    let mut checkpoint = match (&args.resume_from, args.resume) {
+        (Some(path), _) => Some(Checkpoint::open(path)?),
+        (None, true) => Some(Checkpoint::open(args.local_dir.join(Checkpoint::FILE_NAME))?),
+        (None, false) => None,
+    };
+    let mut uploaded = Vec::new();
+    let mut failed = Vec::new();
+    for (local_path, target) in &plan.uploads {
+        if checkpoint.as_ref().is_some_and(|checkpoint| checkpoint.is_done(local_path, target)) {
+            continue;
+        }
+        if !opts.json {
+            println!("{} -> {}", local_path.display(), target);
+        }
+        match upload(opts, &client, local_path, target).await {
+            Ok(()) => {
+                if let Some(checkpoint) = &mut checkpoint {
+                    checkpoint.record(local_path, target)?;
+                }
+                uploaded.push(target.clone());
+            }
+            Err(err) => {
+                eprintln!("Failed {}: {}", local_path.display(), err);
+                failed.push(target.clone());
+                if opts.on_error == OnError::Abort {
+                    break;
+                }
+            }
+        }
+    }
+
+    // Stopping short leaves the deletions too, so nothing is removed that a file
+    // yet to be uploaded might have replaced.
+    let mut deleted = Vec::new();
+    if failed.is_empty() || opts.on_error == OnError::Continue {
+        for (parent, names) in &group_by_parent(plan.deletes.iter().map(String::as_str)) {
+            let paths = names.iter().map(|name| join(parent, name));
+            match client.remove(parent, names).await {
+                Ok(()) => {
+                    if !opts.json {
+                        for path in paths.clone() {
+                            println!("Deleted {}", path);
+                        }
+                    }
+                    deleted.extend(paths);
+                }
+                Err(err) => {
+                    eprintln!("Failed to delete in {}: {}", parent, err);
+                    failed.extend(paths);
+                    if opts.on_error == OnError::Abort {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    if failed.is_empty() {
+        if let Some(checkpoint) = checkpoint {
+            checkpoint.remove()?;
+        }
+    }
+    let failures = failed.len();
+    if opts.json {
+        print_json(&SyncResult {
+            status: if failures == 0 { "ok" } else { "failed" },
+            uploaded,
+            unchanged: plan.unchanged,
+            deleted,
+            failed,
+        })?;
+    } else {
+        println!(
+            "{} uploaded, {} unchanged, {} deleted",
+            uploaded.len(),
+            plan.unchanged,
+            deleted.len()
+        );
+    }
+    if failures > 0 {
+        return Err(AlistError::PartialFailure {
+            failed: failures,
+            total: plan.uploads.len() + plan.deletes.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Compare the local directory with the remote one. Only reads from the server.
+async fn plan(client: &AlistClient, args: &SyncArgs, remote_dir: &str) -> Result<SyncPlan> {
+    let filter = Filter::default().with_newer_than(args.newer_than);
+    let local = walk(&args.local_dir, &filter, args.follow_symlinks)?;
+    let mut plan = SyncPlan::default();
+    let remote = if client.exists(remote_dir).await? {
+        list_tree(client, remote_dir, None).await?
+    } else {
+        plan.mkdirs.push(remote_dir.to_string());
+        RemoteTree::default()
+    };
+
+    let local_dirs: HashSet<String> = local.dirs.iter().map(|dir| join_relative(remote_dir, dir)).collect();
+    for dir in &local.dirs {
+        let target = join_relative(remote_dir, dir);
+        if !remote.dirs.contains(&target) {
+            plan.mkdirs.push(target);
+        }
+    }
+
+    let mut cache = args.checksum.then(|| HashCache::load(&args.local_dir));
+    let mut local_files = HashSet::new();
+    for file in &local.files {
+        if file == Path::new(HashCache::FILE_NAME) || file == Path::new(Checkpoint::FILE_NAME) {
+            continue;
+        }
+        let target = join_relative(remote_dir, file);
+        let remote = remote.files.get(&target);
+        if needs_upload(client, args, cache.as_mut(), file, &target, remote).await? {
+            plan.uploads.push((args.local_dir.join(file), target.clone()));
+        } else {
+            plan.unchanged += 1;
+        }
+        local_files.insert(target);
+    }
+    if let Some(cache) = &cache {
+        if let Err(err) = cache.save() {
+            warn!("Could not save the hash cache: {}", err);
+        }
+    }
+
+    if args.delete {
+        // Only remove the topmost stale entries; removing a directory takes its
+        // contents with it.
+        let root = join_relative(remote_dir, Path::new(""));
+        let kept = |path: &str| {
+            let parent = split_parent(path).0;
+            parent == root || local_dirs.contains(parent)
+        };
+        plan.deletes = remote
+            .dirs
+            .iter()
+            .filter(|dir| !local_dirs.contains(*dir))
+            .chain(remote.files.keys().filter(|file| !local_files.contains(*file)))
+            .filter(|path| kept(path))
+            .cloned()
+            .collect();
+        plan.deletes.sort();
+    }
+    Ok(plan)
+}
+
+/// List everything below `root`, one directory at a time, going at most `max_depth`
+/// directories deep if given; the subdirectories found at that depth are listed
+/// among the dirs but not entered.
+pub(super) async fn list_tree(
+    client: &AlistClient,
+    root: &str,
+    max_depth: Option<usize>,
+) -> Result<RemoteTree> {
+    let mut tree = RemoteTree::default();
+    let mut pending = vec![(root.to_string(), 0)];
+    while let Some((dir, depth)) = pending.pop() {
+        for entry in client.list(&dir).await? {
+            let path = join(&dir, &entry.name);
+            if entry.is_dir {
+                tree.dirs.insert(path.clone());
+                if max_depth.is_none_or(|max| depth < max) {
+                    pending.push((path, depth + 1));
+                }
+            } else {
+                tree.files.insert(path, entry);
+            }
+        }
+    }
+    Ok(tree)
+}
+
+/// Decide whether the local file at `file`, relative to the local directory, differs
+/// from its remote copy at `target`.
+///
+/// A file is changed when the sizes differ. Otherwise, with `--checksum` (and so a
+/// `cache`), the hashes are compared when the server reports one; failing that, a file
+/// is changed when it was modified locally after the remote copy was.
+async fn needs_upload(
+    client: &AlistClient,
+    args: &SyncArgs,
+    cache: Option<&mut HashCache>,
+    file: &Path,
+    target: &str,
+    remote: Option<&FileEntry>,
+) -> Result<bool> {
+    let Some(remote) = remote else {
+        return Ok(true);
+    };
+    if remote.is_dir {
+        return Ok(true);
+    }
+
+    let metadata = tokio::fs::metadata(args.local_dir.join(file)).await?;
+    if metadata.len() != remote.size {
+        return Ok(true);
+    }
+
+    if let Some(cache) = cache {
+        if let Some((kind, remote_hash)) = remote_hash(client, args.hash, target, remote).await? {
+            let local_hash = cache.hash(file, kind).await?;
+            return Ok(!remote_hash.eq_ignore_ascii_case(&local_hash));
+        }
+    }
+    let local_mtime = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() as i64);
+    Ok(match DateTime::parse_from_rfc3339(&remote.modified) {
+        Ok(remote_mtime) => local_mtime > remote_mtime.timestamp(),
+        Err(_) => false,
+    })
+}
+
+/// The hash to compare the remote file at `target` by: of `kind`, or else the
+/// strongest kind the server reports. Listings often leave hashes out, so `fs/get` is asked
+/// when `remote` has none.
+async fn remote_hash(
+    client: &AlistClient,
+    kind: Option<HashKind>,
+    target: &str,
+    remote: &FileEntry,
+) -> Result<Option<(HashKind, String)>> {
+    let pick = |hashes: &HashInfo| hashes.pick(kind);
+    if let Some(found) = remote.hash_info.as_ref().and_then(pick) {
+        return Ok(Some(found));
+    }
+    Ok(client.get(target).await?.hash_info.as_ref().and_then(pick))
+}
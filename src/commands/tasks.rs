@@ -0,0 +1,160 @@
+use alist_cli::model::TaskInfo;
+use alist_cli::{AlistClient, AlistError, Result};
+use reqwest::StatusCode;
+use serde::Serialize;
+
+use super::{connect, print_json, print_planned};
+use crate::cli::{GlobalOpts, OutputFormat, TaskIdArgs, TasksCommand, TasksListArgs};
+
+/// A task together with the manager it belongs to.
+#[derive(Serialize)]
+struct KindTask {
+    kind: &'static str,
+    #[serde(flatten)]
+    task: TaskInfo,
+    state_name: &'static str,
+}
+
+/// `--json` result of `tasks list`.
+#[derive(Serialize)]
+struct TasksResult {
+    status: &'static str,
+    tasks: Vec<KindTask>,
+}
+
+/// `--json` result of `tasks cancel` and `tasks retry`.
+#[derive(Serialize)]
+struct TaskActionResult<'a> {
+    status: &'static str,
+    id: &'a str,
+    kind: &'static str,
+}
+
+#[derive(Clone, Copy)]
+enum TaskAction {
+    Cancel,
+    Retry,
+}
+
+impl TaskAction {
+    fn name(self) -> &'static str {
+        match self {
+            TaskAction::Cancel => "cancel",
+            TaskAction::Retry => "retry",
+        }
+    }
+
+    async fn run(self, client: &AlistClient, kind: &str, id: &str) -> Result<()> {
+        match self {
+            TaskAction::Cancel => client.cancel_task(kind, id).await,
+            TaskAction::Retry => client.retry_task(kind, id).await,
+        }
+    }
+}
+
+pub async fn cmd_tasks(opts: &GlobalOpts, command: &TasksCommand) -> Result<()> {
+    match command {
+        TasksCommand::List(args) => cmd_list(opts, args).await,
+        TasksCommand::Cancel(args) => cmd_action(opts, args, TaskAction::Cancel).await,
+        TasksCommand::Retry(args) => cmd_action(opts, args, TaskAction::Retry).await,
+    }
+}
+
+/// The task kinds to look at: the one asked for, or every kind.
+fn kinds(kind: Option<&str>) -> Result<Vec<&'static str>> {
+    match kind {
+        None => Ok(TaskInfo::KINDS.to_vec()),
+        Some(kind) => match TaskInfo::KINDS.iter().find(|known| **known == kind) {
+            Some(known) => Ok(vec![*known]),
+            None => Err(AlistError::Config(format!(
+                "unknown task kind `{}`, expected one of {}",
+                kind,
+                TaskInfo::KINDS.join(", ")
+            ))),
+        },
+    }
+}
+
+/// Whether the server has no task manager, or no such task, for a kind. Older
+/// servers lack some kinds altogether.
+fn is_missing(err: &AlistError) -> bool {
+    match err.root() {
+        AlistError::Http(err) => err.status() == Some(StatusCode::NOT_FOUND),
+        err => err.is_not_found(),
+    }
+}
+
+async fn cmd_list(opts: &GlobalOpts, args: &TasksListArgs) -> Result<()> {
+    let kinds = kinds(args.kind.as_deref())?;
+    let (client, _) = connect(opts, args.alist_url.as_deref().unwrap_or("/")).await?;
+
+    let mut tasks = Vec::new();
+    for kind in kinds {
+        match client.tasks(kind, args.done).await {
+            Ok(found) => tasks.extend(found.into_iter().map(|task| KindTask {
+                kind,
+                state_name: task.state_name(),
+                task,
+            })),
+            Err(err) if args.kind.is_none() && is_missing(&err) => continue,
+            Err(err) => return Err(err),
+        }
+    }
+
+    match args.output.unwrap_or(if opts.json { OutputFormat::Json } else { OutputFormat::Table }) {
+        OutputFormat::Json => return print_json(&TasksResult { status: "ok", tasks }),
+        OutputFormat::Plain => {
+            for KindTask { task, .. } in &tasks {
+                println!("{}", task.id);
+            }
+            return Ok(());
+        }
+        OutputFormat::Table => {}
+    }
+    if tasks.is_empty() {
+        println!("No {} tasks", if args.done { "finished" } else { "pending" });
+        return Ok(());
+    }
+    println!("{:<24} {:<25} {:<13} {:>5}  NAME", "ID", "KIND", "STATE", "DONE");
+    for KindTask { kind, task, state_name } in &tasks {
+        println!(
+            "{:<24} {:<25} {:<13} {:>4.0}%  {}",
+            task.id, kind, state_name, task.progress, task.name
+        );
+        if !task.error.is_empty() {
+            println!("{:<24} {}", "", task.error);
+        }
+    }
+    Ok(())
+}
+
+/// Cancel or retry a task, finding its kind first when `--kind` is not given.
+async fn cmd_action(opts: &GlobalOpts, args: &TaskIdArgs, action: TaskAction) -> Result<()> {
+    let kinds = kinds(args.kind.as_deref())?;
+    let (client, _) = connect(opts, args.alist_url.as_deref().unwrap_or("/")).await?;
+    if opts.dry_run {
+        return print_planned(opts, action.name(), &args.id, None);
+    }
+
+    for kind in kinds {
+        match action.run(&client, kind, &args.id).await {
+            Ok(()) => {
+                if opts.json {
+                    return print_json(&TaskActionResult {
+                        status: "ok",
+                        id: &args.id,
+                        kind,
+                    });
+                }
+                println!("Sent {} to {} task {}", action.name(), kind, args.id);
+                return Ok(());
+            }
+            Err(err) if args.kind.is_none() && is_missing(&err) => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Err(AlistError::Api {
+        code: 404,
+        message: format!("task {} not found", args.id),
+    })
+}
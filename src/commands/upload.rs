@@ -0,0 +1,1098 @@
+use alist_cli::checkpoint::Checkpoint;
+use alist_cli::client::{PutOptions, Transport, UploadMode};
+use alist_cli::crypt::{self, Passphrase};
+use alist_cli::hash::{hash_file, HashKind, StreamHasher};
+use alist_cli::model::{ApiResponse, PutData};
+use alist_cli::part_manifest::PartManifest;
+use alist_cli::path::{dest_path, encode_path, join, join_relative, split_parent};
+use alist_cli::progress::{transfer_bar, ProgressFormat, TotalProgress};
+use alist_cli::{AlistClient, AlistError, Result};
+use futures_util::stream::{self, StreamExt};
+use indicatif::{MultiProgress, ProgressBar};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs::Metadata;
+use std::future::Future;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncRead, ReadBuf};
+use tracing::{debug, warn};
+
+use super::audit;
+use super::filter::{is_glob, walk, Filter};
+use super::fs::free_name;
+use super::{
+    connect, connect_webdav, multi_progress, plain_path, print_json, print_planned, progress_refresh,
+    read_passphrase, record_upload, total_progress, track_temporary, transfer_progress, untrack_temporary,
+    wait_for_task,
+};
+use crate::cli::{GlobalOpts, OnConflict, OnError, UploadArgs, UploadOptions};
+
+pub async fn cmd_upload(opts: &GlobalOpts, args: &UploadArgs) -> Result<()> {
+    let from_stdin = args.local_files.iter().any(|file| file == "-");
+    if from_stdin && args.local_files.len() > 1 {
+        return Err(AlistError::Config(
+            "`-` cannot be combined with other local paths".to_string(),
+        ));
+    }
+    if from_stdin && opts.password.as_deref() == Some("-") {
+        return Err(AlistError::Config(
+            "cannot read both the password and the upload from stdin".to_string(),
+        ));
+    }
+
+    if args.stdin_size.is_some() && !from_stdin {
+        return Err(AlistError::Config("--stdin-size only applies when uploading `-`".to_string()));
+    }
+
+    if let Some(name) = &args.dest_name {
+        if args.local_files.len() > 1 || is_glob(&args.local_files[0]) {
+            return Err(AlistError::Config("--dest-name only works with a single local path".to_string()));
+        }
+        if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+            return Err(AlistError::InvalidPath(format!(
+                "`{}` is not a file name; --dest-name cannot contain slashes",
+                name
+            )));
+        }
+    }
+
+    let (client, mut remote_path) = match args.options.transport {
+        Transport::Api => connect(opts, &args.alist_url).await?,
+        Transport::WebDav => {
+            check_webdav(&args.options)?;
+            connect_webdav(opts, &args.alist_url)?
+        }
+    };
+    if let Some(name) = &args.dest_name {
+        remote_path = join(&remote_path, name);
+    } else if let (None, [local_file]) = (&args.relative_to, args.local_files.as_slice()) {
+        // Like `cp`, a single file uploaded to a directory goes inside it.
+        let local_path = Path::new(local_file);
+        let is_file = !from_stdin && !is_glob(local_file) && local_metadata(local_path).await?.is_file();
+        if let (true, Some(name)) = (is_file, local_path.file_name()) {
+            let into_dir = args.dest_is_dir
+                || args.alist_url.ends_with('/')
+                || (args.options.transport == Transport::Api && client.is_dir(&remote_path).await?);
+            remote_path = dest_path(&remote_path, &name.to_string_lossy(), into_dir);
+        }
+    }
+    let uploader = Uploader::new(opts, &args.options, client)?;
+
+    if from_stdin {
+        return uploader.upload_stdin(&remote_path, args.stdin_size).await;
+    }
+    let mut plan = uploader
+        .plan(&args.local_files, args.allow_empty_glob, args.relative_to.as_deref(), &remote_path)
+        .await?;
+    uploader.add_suffixes(&mut plan.jobs);
+    uploader.check_sizes(&plan.jobs).await?;
+    if opts.dry_run {
+        return plan.print(opts);
+    }
+    for dir in &plan.dirs {
+        uploader.client.ensure_dir(dir).await?;
+    }
+    uploader.run(plan.jobs).await
+}
+
+/// Where `local` lies below the local directory `base`, for `--relative-to`.
+fn relative_path(local: &Path, base: &Path) -> Result<PathBuf> {
+    let plain = |path: &Path| -> PathBuf {
+        path.components().filter(|component| *component != Component::CurDir).collect()
+    };
+    match plain(local).strip_prefix(plain(base)) {
+        Ok(relative) => Ok(relative.to_path_buf()),
+        Err(_) => Err(AlistError::InvalidPath(format!(
+            "{} is not below --relative-to {}",
+            local.display(),
+            base.display()
+        ))),
+    }
+}
+
+/// Uploads at least this large in total check the login before they start.
+const PREFLIGHT_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Refuse options that need the REST API when uploading over WebDAV.
+pub(super) fn check_webdav(options: &UploadOptions) -> Result<()> {
+    let unsupported = [
+        (options.as_task, "--as-task"),
+        (options.upload_mode == UploadMode::Form, "--upload-mode form"),
+        (options.verify, "--verify"),
+        (options.no_overwrite, "--no-overwrite"),
+        (options.skip_existing, "--skip-existing"),
+        (options.skip_unchanged, "--skip-unchanged"),
+        (options.on_conflict.is_some_and(|policy| policy != OnConflict::Overwrite), "--on-conflict"),
+        (options.atomic, "--atomic"),
+        (options.send_hash.is_some(), "--send-hash"),
+        (options.move_after.is_some(), "--move-after"),
+        (options.wait_for_index, "--wait-for-index"),
+    ];
+    match unsupported.iter().find(|(given, _)| *given) {
+        Some((_, option)) => Err(AlistError::Config(format!(
+            "{} is not supported with --transport webdav",
+            option
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// The metadata of a local file to upload, with errors that name it, such as
+/// `photos/a.jpg: no such file or directory`.
+pub(super) async fn local_metadata(path: &Path) -> Result<Metadata> {
+    tokio::fs::metadata(path).await.map_err(|err| {
+        let reason = match err.kind() {
+            io::ErrorKind::NotFound => "no such file or directory".to_string(),
+            io::ErrorKind::PermissionDenied => "permission denied".to_string(),
+            _ => err.to_string(),
+        };
+        AlistError::Io(io::Error::new(err.kind(), format!("{}: {}", path.display(), reason)))
+    })
+}
+
+fn too_large(local: &str, size: u64, max: u64) -> AlistError {
+    AlistError::Config(format!(
+        "{} is {} bytes, more than --max-upload-size allows ({} bytes)",
+        local, size, max
+    ))
+}
+
+/// Extensions of files whose contents are compressed already, which `--gzip` would
+/// not make smaller.
+const COMPRESSED_EXTENSIONS: &[&str] = &[
+    "gz", "tgz", "zip", "7z", "rar", "xz", "txz", "bz2", "zst", "lz4", "br", "jpg", "jpeg", "png", "gif",
+    "webp", "heic", "avif", "mp3", "m4a", "aac", "ogg", "opus", "flac", "mp4", "m4v", "mkv", "mov", "avi",
+    "webm", "docx", "xlsx", "pptx", "apk", "jar",
+];
+
+/// Reads from `inner`, failing once more than `remaining` bytes have come through.
+struct Capped<R> {
+    inner: R,
+    remaining: u64,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Capped<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        let read = (buf.filled().len() - before) as u64;
+        match self.remaining.checked_sub(read) {
+            Some(remaining) => {
+                self.remaining = remaining;
+                Poll::Ready(Ok(()))
+            }
+            None => Poll::Ready(Err(io::Error::other(
+                "the input is larger than --max-upload-size allows",
+            ))),
+        }
+    }
+}
+
+/// A local file and the remote path it is uploaded to.
+pub(super) struct Job {
+    pub local: PathBuf,
+    pub remote: String,
+    /// Whether to replace an existing remote file, when not left to the options.
+    pub overwrite: Option<bool>,
+}
+
+/// What an upload is going to do: create `dirs` in order, then upload `jobs`.
+#[derive(Default)]
+pub(super) struct Plan {
+    pub dirs: Vec<String>,
+    pub jobs: Vec<Job>,
+}
+
+impl Plan {
+    /// Describe the plan for `--dry-run`.
+    pub fn print(&self, opts: &GlobalOpts) -> Result<()> {
+        for dir in &self.dirs {
+            print_planned(opts, "mkdir", dir, None)?;
+        }
+        for job in &self.jobs {
+            print_planned(opts, "upload", &job.local.to_string_lossy(), Some(&job.remote))?;
+        }
+        Ok(())
+    }
+}
+
+/// `--json` result for one uploaded file.
+#[derive(Serialize)]
+struct UploadResult<'a> {
+    /// `ok`, `started` (as a background task), `skipped` or `failed`.
+    status: &'static str,
+    path: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    task_id: Option<String>,
+    /// The verified hash, with `--verify`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl<'a> UploadResult<'a> {
+    fn new(status: &'static str, path: &'a str) -> Self {
+        UploadResult {
+            status,
+            path,
+            size: None,
+            task_id: None,
+            hash: None,
+            error: None,
+        }
+    }
+}
+
+/// How one upload ended, as reported to `--webhook`: the status, path and size of
+/// its [`UploadResult`].
+struct Outcome {
+    status: &'static str,
+    path: String,
+    size: Option<u64>,
+}
+
+impl From<&UploadResult<'_>> for Outcome {
+    fn from(result: &UploadResult) -> Self {
+        Outcome {
+            status: result.status,
+            path: result.path.to_string(),
+            size: result.size,
+        }
+    }
+}
+
+/// What `--webhook` is sent for one upload.
+#[derive(Serialize)]
+struct WebhookPayload<'a> {
+    /// `ok`, `started` (as a background task), `skipped` or `failed`.
+    status: &'static str,
+    path: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    duration_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// State shared by every file of one `upload` or `batch` invocation.
+pub(super) struct Uploader<'a> {
+    opts: &'a GlobalOpts,
+    options: &'a UploadOptions,
+    pub client: Arc<AlistClient>,
+    filter: Filter,
+    bars: MultiProgress,
+    /// The bar for all files together, once several are uploading.
+    total: OnceLock<TotalProgress>,
+    /// Where finished uploads are recorded, with `batch --resume`.
+    checkpoint: Mutex<Option<Checkpoint>>,
+    /// What `--encrypt` derives the key of each file from.
+    passphrase: Option<Passphrase>,
+}
+
+impl<'a> Uploader<'a> {
+    pub fn new(opts: &'a GlobalOpts, options: &'a UploadOptions, client: AlistClient) -> Result<Self> {
+        if options.resumable && options.transport != Transport::WebDav {
+            return Err(AlistError::Config(
+                "--resumable needs --transport webdav; the API cannot take part of a file".to_string(),
+            ));
+        }
+        let passphrase = if options.encrypt && !opts.dry_run {
+            Some(read_passphrase(true)?)
+        } else {
+            None
+        };
+        Ok(Uploader {
+            opts,
+            options,
+            client: Arc::new(client),
+            filter: Filter::new(&options.include, &options.exclude, options.ignore_file.as_deref())?
+                .with_newer_than(options.newer_than)
+                .with_max_depth(options.max_depth),
+            bars: multi_progress(opts),
+            total: OnceLock::new(),
+            checkpoint: Mutex::new(None),
+            passphrase,
+        })
+    }
+
+    /// Skip the jobs `checkpoint` records as done, and record each one that finishes.
+    pub fn with_checkpoint(self, checkpoint: Checkpoint) -> Self {
+        *self.checkpoint.lock().expect("checkpoint lock") = Some(checkpoint);
+        self
+    }
+
+    /// Note that `job` is done in the checkpoint, if there is one.
+    fn record(&self, job: &Job) -> Result<()> {
+        match self.checkpoint.lock().expect("checkpoint lock").as_mut() {
+            Some(checkpoint) => checkpoint.record(&job.local, &job.remote),
+            None => Ok(()),
+        }
+    }
+
+    /// Run `upload` of `remote_path`, then tell `--webhook` how it went: the outcome
+    /// it reports, or else the error. An upload that failed keeps its own error whether
+    /// or not the webhook can be reached. An upload with no outcome, as in a dry run,
+    /// is not reported.
+    async fn notified(
+        &self,
+        remote_path: &str,
+        upload: impl Future<Output = Result<Option<Outcome>>>,
+    ) -> Result<()> {
+        let started = Instant::now();
+        let result = upload.await;
+        let Some(webhook) = &self.options.webhook else {
+            return result.map(drop);
+        };
+        let payload = match &result {
+            Ok(None) => return Ok(()),
+            Err(_) if !self.options.webhook_on_failure => return result.map(drop),
+            Ok(Some(outcome)) => WebhookPayload {
+                status: outcome.status,
+                path: &outcome.path,
+                size: outcome.size,
+                duration_ms: started.elapsed().as_millis() as u64,
+                error: None,
+            },
+            Err(err) => WebhookPayload {
+                status: "failed",
+                path: remote_path,
+                size: None,
+                duration_ms: started.elapsed().as_millis() as u64,
+                error: Some(err.to_string()),
+            },
+        };
+        match self.client.post_webhook(webhook, &payload).await {
+            Err(err) if self.options.webhook_strict && result.is_ok() => Err(err),
+            Err(err) => {
+                warn!("Could not call the webhook for {}: {}", payload.path, err);
+                result.map(drop)
+            }
+            Ok(()) => result.map(drop),
+        }
+    }
+
+    /// Upload `job`, calling `--webhook` afterwards.
+    async fn upload_job(&self, job: &Job) -> Result<()> {
+        self.notified(&job.remote, self.upload_file(job)).await
+    }
+
+    fn progress(&self, remote_path: &str, size: Option<u64>) -> ProgressBar {
+        if self.opts.no_progress || self.opts.progress_format == ProgressFormat::Json {
+            transfer_progress(self.opts, remote_path, size)
+        } else if let Some(total) = self.total.get() {
+            total.transfer(size)
+        } else {
+            self.bars.add(transfer_bar(size))
+        }
+    }
+
+    /// Whether `--gzip` compresses the file at `path`.
+    fn compresses(&self, path: &Path) -> bool {
+        let compressed = path.extension().and_then(|extension| extension.to_str()).is_some_and(|extension| {
+            COMPRESSED_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+        });
+        self.options.gzip && (self.options.force_gzip || !compressed)
+    }
+
+    /// Add `.gz` to the remote paths of the files `--gzip` compresses, and `.enc` to
+    /// those of the files `--encrypt` encrypts.
+    pub fn add_suffixes(&self, jobs: &mut [Job]) {
+        for job in jobs {
+            if self.compresses(&job.local) {
+                job.remote.push_str(".gz");
+            } else if self.options.encrypt {
+                job.remote = format!("{}.{}", job.remote, crypt::EXTENSION);
+            }
+        }
+    }
+
+    /// Refuse the whole upload if any file is larger than `--max-upload-size`.
+    pub async fn check_sizes(&self, jobs: &[Job]) -> Result<()> {
+        let Some(max) = self.options.max_upload_size else {
+            return Ok(());
+        };
+        for job in jobs {
+            let size = local_metadata(&job.local).await?.len();
+            if size > max {
+                return Err(too_large(&job.local.to_string_lossy(), size, max));
+            }
+        }
+        Ok(())
+    }
+
+    /// Upload piped input to the remote path, which must name the file itself, calling
+    /// `--webhook` afterwards.
+    async fn upload_stdin(&self, remote_path: &str, size: Option<u64>) -> Result<()> {
+        // Without a local file, the remote name tells whether the input is compressed.
+        let gzip = self.compresses(Path::new(remote_path));
+        let remote_path = &if gzip {
+            format!("{}.gz", remote_path)
+        } else if self.options.encrypt {
+            format!("{}.{}", remote_path, crypt::EXTENSION)
+        } else {
+            remote_path.to_string()
+        };
+        self.preflight(size).await?;
+        self.notified(remote_path, self.put_stdin(remote_path, size, gzip)).await
+    }
+
+    /// Before uploading `size` bytes, or an unknown amount, check with `me` that the
+    /// server takes the login, so a rejected token fails the run at once rather than
+    /// after the data is sent. Small uploads are not worth the extra request, and over
+    /// WebDAV there is no login to check.
+    async fn preflight(&self, size: Option<u64>) -> Result<()> {
+        let small = size.is_some_and(|size| size < PREFLIGHT_SIZE);
+        if small || self.options.no_preflight || self.options.transport == Transport::WebDav {
+            return Ok(());
+        }
+        let user = self.client.me().await?;
+        debug!("Logged in as {}, starting the upload", user.username);
+        Ok(())
+    }
+
+    /// Upload piped input to `remote_path`, compressed with `gzip`. Unless
+    /// `--stdin-size` gives the size, the upload is sent chunked.
+    async fn put_stdin(&self, remote_path: &str, size: Option<u64>, gzip: bool) -> Result<Option<Outcome>> {
+        if let (Some(size), Some(max)) = (size, self.options.max_upload_size) {
+            if size > max {
+                return Err(too_large("stdin", size, max));
+            }
+        }
+        if self.opts.dry_run {
+            let parent = split_parent(remote_path).0.to_string();
+            let plan = Plan {
+                dirs: self.options.mkdir.then_some(parent).into_iter().collect(),
+                jobs: vec![Job {
+                    local: PathBuf::from("-"),
+                    remote: remote_path.to_string(),
+                    overwrite: None,
+                }],
+            };
+            plan.print(self.opts)?;
+            return Ok(None);
+        }
+        if self.options.mkdir {
+            self.client.ensure_dir(split_parent(remote_path).0).await?;
+        }
+        let Some(remote_path) = self.destination(remote_path, None).await? else {
+            return Ok(Some(Outcome::from(&UploadResult::new("skipped", remote_path))));
+        };
+        let remote_path = remote_path.as_str();
+        if self.options.send_hash.is_some() {
+            warn!("--send-hash cannot hash stdin before uploading it; sending no hash");
+        }
+        let progress = self.progress(remote_path, size);
+        let put = PutOptions {
+            gzip,
+            ..self.put_options(remote_path)
+        };
+        let (put_ref, progress_ref) = (&put, &progress);
+        let started = Instant::now();
+        let response = self
+            .put_to(remote_path, |target| async move {
+                let stdin = Capped {
+                    inner: tokio::io::stdin(),
+                    remaining: self.options.max_upload_size.unwrap_or(u64::MAX),
+                };
+                self.client.put_reader(stdin, size, &target, put_ref, progress_ref).await
+            })
+            .await?;
+        record_upload(progress.position(), started.elapsed());
+        self.finish(response, remote_path, progress.position(), &put).await.map(Some)
+    }
+
+    /// Options for the file going to `remote_path`, with a fresh hasher when verifying.
+    fn put_options(&self, remote_path: &str) -> PutOptions {
+        // A temporary name would make the server guess the type from `.part`.
+        let content_type = match &self.options.content_type {
+            None if self.options.atomic => {
+                Some(mime_guess::from_path(remote_path).first_or_octet_stream().to_string())
+            }
+            content_type => content_type.clone(),
+        };
+        PutOptions {
+            mode: self.options.upload_mode,
+            as_task: self.options.as_task,
+            hasher: self.options.verify.then(|| match self.options.hash {
+                Some(kind) => StreamHasher::new(kind),
+                None => StreamHasher::of(&HashKind::ALL),
+            }),
+            content_type,
+            modified: None,
+            file_hash: None,
+            gzip: false,
+            encrypt: self.passphrase.clone(),
+        }
+    }
+
+    /// Run `upload` with the path to send the file to: `remote_path` itself, or with
+    /// `--atomic` a temporary name next to it, which is renamed to `remote_path` once
+    /// the upload succeeded and removed if it failed or is interrupted. Should the
+    /// rename fail, the complete upload is left under its temporary name.
+    async fn put_to<F, Fut>(&self, remote_path: &str, upload: F) -> Result<ApiResponse<PutData>>
+    where
+        F: FnOnce(String) -> Fut,
+        Fut: Future<Output = Result<ApiResponse<PutData>>>,
+    {
+        if !self.options.atomic {
+            return upload(remote_path.to_string()).await;
+        }
+        let (parent, name) = split_parent(remote_path);
+        let temporary_name = temporary_name(name);
+        let temporary = join(parent, &temporary_name);
+        track_temporary(&self.client, &temporary);
+        match upload(temporary.clone()).await {
+            Ok(response) => {
+                untrack_temporary(&temporary);
+                if let Err(err) = self.move_into_place(&temporary, remote_path).await {
+                    warn!("The upload is complete but left as {}", temporary);
+                    return Err(err);
+                }
+                Ok(response)
+            }
+            Err(err) => {
+                if let Err(err) = self.client.remove(parent, &[temporary_name]).await {
+                    warn!("Could not remove the partial upload {}: {}", temporary, err);
+                }
+                untrack_temporary(&temporary);
+                Err(err)
+            }
+        }
+    }
+
+    /// Rename the finished upload at `temporary` to `remote_path`, replacing what
+    /// is there.
+    async fn move_into_place(&self, temporary: &str, remote_path: &str) -> Result<()> {
+        let (parent, name) = split_parent(remote_path);
+        if self.client.exists(remote_path).await? {
+            self.client.remove(parent, &[name.to_string()]).await?;
+        }
+        self.client.rename(temporary, name).await
+    }
+
+    /// Where a file meant for `remote_path` is uploaded to under the overwrite policy, or
+    /// `overwrite` if given: `remote_path` itself, a free numbered name next to it with
+    /// `--on-conflict rename`, or `None` to skip it.
+    async fn destination(&self, remote_path: &str, overwrite: Option<bool>) -> Result<Option<String>> {
+        if overwrite.is_some() || self.options.on_conflict != Some(OnConflict::Rename) {
+            let upload = self.should_upload(remote_path, overwrite).await?;
+            return Ok(upload.then(|| remote_path.to_string()));
+        }
+        if !self.client.exists(remote_path).await? {
+            return Ok(Some(remote_path.to_string()));
+        }
+        let (parent, name) = split_parent(remote_path);
+        let renamed = join(parent, &free_name(&self.client, name, false, &[parent], &HashSet::new()).await?);
+        if !self.opts.json {
+            eprintln!("{} already exists; uploading as {}", remote_path, renamed);
+        }
+        Ok(Some(renamed))
+    }
+
+    /// Apply the overwrite policy to `remote_path`, or `overwrite` if given: `Ok(false)`
+    /// means skip it.
+    async fn should_upload(&self, remote_path: &str, overwrite: Option<bool>) -> Result<bool> {
+        let (no_overwrite, skip_existing) = match overwrite {
+            Some(overwrite) => (!overwrite, false),
+            None => (
+                self.options.no_overwrite || self.options.on_conflict == Some(OnConflict::Error),
+                self.options.skip_existing || self.options.on_conflict == Some(OnConflict::Skip),
+            ),
+        };
+        if !(no_overwrite || skip_existing) {
+            return Ok(true);
+        }
+        if !self.client.exists(remote_path).await? {
+            return Ok(true);
+        }
+        if skip_existing {
+            audit::record_file(remote_path, "skipped", None, None);
+            if self.opts.json {
+                print_json(&UploadResult::new("skipped", remote_path))?;
+            } else {
+                eprintln!("Skipping {} (already exists)", remote_path);
+            }
+            return Ok(false);
+        }
+        Err(AlistError::AlreadyExists(remote_path.to_string()))
+    }
+
+    async fn upload_file(&self, job: &Job) -> Result<Option<Outcome>> {
+        let local_path = job.local.as_path();
+        let Some(remote_path) = self.destination(&job.remote, job.overwrite).await? else {
+            return Ok(Some(Outcome::from(&UploadResult::new("skipped", &job.remote))));
+        };
+        let remote_path = remote_path.as_str();
+        if self.options.skip_unchanged
+            && self.client.has_same_file(remote_path, local_path, self.options.hash).await?
+        {
+            audit::record_file(remote_path, "skipped", None, None);
+            if self.opts.json {
+                print_json(&UploadResult::new("skipped", remote_path))?;
+            } else {
+                eprintln!("Skipping {} (unchanged)", remote_path);
+            }
+            return Ok(Some(Outcome::from(&UploadResult::new("skipped", remote_path))));
+        }
+        let metadata = local_metadata(local_path).await?;
+        let sent = if self.options.encrypt {
+            crypt::encrypted_size(metadata.len())
+        } else {
+            metadata.len()
+        };
+        let progress = self.progress(remote_path, Some(sent));
+        let mut put = self.put_options(remote_path);
+        put.gzip = self.compresses(local_path);
+        if self.options.preserve_mtime {
+            match metadata.modified() {
+                Ok(modified) => put.modified = Some(modified),
+                Err(err) => warn!("Cannot read the modification time of {}: {}", local_path.display(), err),
+            }
+        }
+        if let Some(kind) = self.options.send_hash {
+            put.file_hash = Some((kind, hash_file(local_path, kind).await?));
+        }
+        let (put_ref, progress_ref) = (&put, &progress);
+        let started = Instant::now();
+        let response = if self.options.resumable
+            && self.upload_in_parts(local_path, remote_path, metadata.len(), &progress).await?
+        {
+            ApiResponse {
+                code: 200,
+                message: "OK".to_string(),
+                data: None,
+            }
+        } else {
+            self.put_to(remote_path, |target| async move {
+                self.client.put_file(local_path, &target, put_ref, progress_ref).await
+            })
+            .await?
+        };
+        record_upload(progress.position(), started.elapsed());
+        let outcome = self.finish(response, remote_path, progress.position(), &put).await?;
+        self.record(job)?;
+        Ok(Some(outcome))
+    }
+
+    /// Upload `local_path` in parts of `--part-size`, noting each part the server has
+    /// stored in the file's `.part` manifest, and carrying on after the last one an
+    /// earlier run noted. `Ok(false)` means the file is small enough to send whole, or
+    /// the server cannot store parts, and has to be uploaded the usual way.
+    async fn upload_in_parts(
+        &self,
+        local_path: &Path,
+        remote_path: &str,
+        size: u64,
+        progress: &ProgressBar,
+    ) -> Result<bool> {
+        let mut manifest = PartManifest::open(local_path, remote_path)?;
+        let mut offset = manifest.offset();
+        if offset == 0 && size <= self.options.part_size {
+            return Ok(false);
+        }
+        if offset > 0 && !self.opts.json {
+            eprintln!("Resuming {} after {} of {} bytes", remote_path, offset, size);
+        }
+        while offset < size {
+            let end = offset.saturating_add(self.options.part_size).min(size);
+            let stored = self
+                .client
+                .put_file_range(local_path, remote_path, offset..end, size, progress)
+                .await?;
+            if !stored {
+                if offset > 0 {
+                    warn!("The server cannot resume {}; uploading it again from the start", remote_path);
+                }
+                manifest.remove()?;
+                return Ok(false);
+            }
+            offset = end;
+            manifest.confirm(offset)?;
+        }
+        progress.finish();
+        manifest.remove()?;
+        Ok(true)
+    }
+
+    /// Work out which files go where and which remote directories that needs,
+    /// without changing anything on the server.
+    ///
+    /// A single local file is uploaded to `remote_path` itself and a single directory
+    /// is mirrored below it. Several local paths, or the matches of a glob pattern,
+    /// are all placed inside `remote_path`.
+    async fn plan(
+        &self,
+        local_files: &[String],
+        allow_empty_glob: bool,
+        relative_to: Option<&Path>,
+        remote_path: &str,
+    ) -> Result<Plan> {
+        let mut plan = Plan::default();
+        if let [local_file] = local_files {
+            if !is_glob(local_file) && relative_to.is_none() {
+                let local_path = Path::new(local_file);
+                if local_metadata(local_path).await?.is_dir() {
+                    self.collect_dir(local_path, remote_path, None, &mut plan)?;
+                } else {
+                    if self.options.mkdir {
+                        plan.dirs.push(split_parent(remote_path).0.to_string());
+                    }
+                    plan.jobs.push(Job {
+                        local: local_path.to_path_buf(),
+                        remote: remote_path.to_string(),
+                        overwrite: None,
+                    });
+                }
+                return Ok(plan);
+            }
+        }
+
+        let sources = expand_sources(local_files, allow_empty_glob)?;
+        if self.options.mkdir {
+            plan.dirs.push(remote_path.to_string());
+        }
+        for local_path in sources {
+            let target = match relative_to {
+                Some(base) => {
+                    let target = join_relative(remote_path, &relative_path(&local_path, base)?);
+                    let parent = split_parent(&target).0;
+                    if self.options.mkdir && !plan.dirs.iter().any(|dir| dir == parent) {
+                        plan.dirs.push(parent.to_string());
+                    }
+                    target
+                }
+                None => {
+                    let name = local_path.file_name().ok_or_else(|| {
+                        AlistError::InvalidPath(format!("{} has no file name", local_path.display()))
+                    })?;
+                    join(remote_path, &name.to_string_lossy())
+                }
+            };
+            if local_metadata(&local_path).await?.is_dir() {
+                self.collect_dir(&local_path, &target, None, &mut plan)?;
+            } else {
+                plan.jobs.push(Job {
+                    local: local_path,
+                    remote: target,
+                    overwrite: None,
+                });
+            }
+        }
+        Ok(plan)
+    }
+
+    /// Queue every file under `local_dir` that passes the filter, mirroring its
+    /// layout below `remote_dir`.
+    pub fn collect_dir(
+        &self,
+        local_dir: &Path,
+        remote_dir: &str,
+        overwrite: Option<bool>,
+        plan: &mut Plan,
+    ) -> Result<()> {
+        let tree = walk(local_dir, &self.filter, self.options.follow_symlinks)?;
+        if remote_dir != "/" {
+            plan.dirs.push(remote_dir.to_string());
+        }
+        plan.dirs.extend(tree.dirs.iter().map(|dir| join_relative(remote_dir, dir)));
+        plan.jobs.extend(tree.files.into_iter().map(|file| Job {
+            remote: join_relative(remote_dir, &file),
+            local: local_dir.join(file),
+            overwrite,
+        }));
+        Ok(())
+    }
+
+    /// Upload `jobs` with up to `--concurrency` transfers at once. A single file
+    /// fails with its own error; otherwise failures are collected into a summary.
+    pub async fn run(&self, mut jobs: Vec<Job>) -> Result<()> {
+        if let Some(checkpoint) = self.checkpoint.lock().expect("checkpoint lock").as_ref() {
+            let before = jobs.len();
+            jobs.retain(|job| !checkpoint.is_done(&job.local, &job.remote));
+            if jobs.len() < before {
+                self.say(format_args!(
+                    "Skipping {} files uploaded by an earlier run, as recorded in {}",
+                    before - jobs.len(),
+                    checkpoint.path().display()
+                ));
+            }
+        }
+        let mut size = 0;
+        for job in &jobs {
+            size += local_metadata(&job.local).await?.len();
+        }
+        self.preflight(Some(size)).await?;
+        let result = self.run_jobs(jobs).await;
+        if result.is_ok() {
+            if let Some(checkpoint) = self.checkpoint.lock().expect("checkpoint lock").take() {
+                checkpoint.remove()?;
+            }
+        }
+        result
+    }
+
+    async fn run_jobs(&self, jobs: Vec<Job>) -> Result<()> {
+        if let [job] = jobs.as_slice() {
+            return self.upload_job(job).await;
+        }
+
+        let total = jobs.len();
+        if let Some(progress) = total_progress(self.opts, &self.bars, total) {
+            let _ = self.total.set(progress);
+        }
+        // Set once a file failed with `--on-error abort`, after which no more start.
+        let aborted = &AtomicBool::new(false);
+        let uploads = stream::iter(jobs)
+            .map(|job| async move {
+                if aborted.load(Ordering::Relaxed) {
+                    return (job, None);
+                }
+                if !self.opts.json {
+                    self.bars
+                        .suspend(|| self.say(format_args!("{} -> {}", job.local.display(), job.remote)));
+                }
+                let result = self.upload_job(&job).await;
+                if result.is_err() && self.opts.on_error == OnError::Abort {
+                    aborted.store(true, Ordering::Relaxed);
+                }
+                if let Some(progress) = self.total.get() {
+                    progress.file_done();
+                }
+                (job, Some(result))
+            })
+            .buffer_unordered(self.options.concurrency.max(1))
+            .collect::<Vec<(Job, Option<Result<()>>)>>();
+        let results = match self.total.get() {
+            Some(progress) => {
+                let refresh = progress_refresh(self.opts);
+                let results = tokio::select! {
+                    results = uploads => results,
+                    () = progress.refresh(refresh) => unreachable!("refreshing never ends"),
+                };
+                progress.finish();
+                results
+            }
+            None => uploads.await,
+        };
+
+        let not_started = results.iter().filter(|(_, result)| result.is_none()).count();
+        let failures: Vec<_> = results
+            .into_iter()
+            .filter_map(|(job, result)| result?.err().map(|err| (job, err)))
+            .collect();
+        for (job, err) in &failures {
+            audit::record_file(&job.remote, "failed", None, Some(err));
+        }
+        if self.opts.json {
+            for (job, err) in &failures {
+                print_json(&UploadResult {
+                    error: Some(err.to_string()),
+                    ..UploadResult::new("failed", &job.remote)
+                })?;
+            }
+        } else {
+            let uploaded = total - failures.len() - not_started;
+            self.say(format_args!("Uploaded {} of {} files", uploaded, total));
+            if not_started > 0 {
+                self.say(format_args!("Stopped after a failure; {} files were not started", not_started));
+            }
+            for (job, err) in &failures {
+                eprintln!("Failed {}: {}", job.local.display(), err);
+            }
+        }
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(AlistError::PartialFailure {
+                failed: failures.len(),
+                total,
+            })
+        }
+    }
+
+    /// Report the server's answer, wait for a background task if asked to, and
+    /// verify the stored file once it is complete.
+    async fn finish(
+        &self,
+        response: ApiResponse<PutData>,
+        remote_path: &str,
+        size: u64,
+        put: &PutOptions,
+    ) -> Result<Outcome> {
+        let moved;
+        let mut result = UploadResult {
+            size: Some(size),
+            ..UploadResult::new("ok", remote_path)
+        };
+        match response.data.and_then(|data| data.task) {
+            Some(task) => {
+                if !self.opts.json {
+                    self.say(format_args!("Started task {}: {}", task.id, task.name));
+                }
+                result.task_id = Some(task.id.clone());
+                if !self.options.wait {
+                    if put.hasher.is_some() {
+                        warn!("Not verifying {}: the upload is still running on the server", remote_path);
+                    }
+                    if self.options.move_after.is_some() {
+                        warn!("Not moving {}: the upload is still running on the server", remote_path);
+                    }
+                    if self.options.wait_for_index {
+                        warn!("Not waiting for {} to be listed: the upload is still running", remote_path);
+                    }
+                    result.status = "started";
+                    return self.emit(&result);
+                }
+                wait_for_task(self.opts, &self.client, "upload", &task).await?;
+            }
+            None if !self.opts.json => {
+                self.say(format_args!("Uploaded {} ({})", remote_path, response.message));
+            }
+            None => {}
+        }
+        if self.options.wait_for_index {
+            let timeout = Duration::from_secs(self.options.index_timeout);
+            if !self.client.wait_listed(remote_path, timeout).await? {
+                warn!(
+                    "{} is not listed in its directory after {} seconds; carrying on",
+                    remote_path, self.options.index_timeout
+                );
+            }
+        }
+        if let Some(hasher) = &put.hasher {
+            match self.verify(remote_path, hasher).await {
+                Ok(hash) => result.hash = hash,
+                Err(err) if self.options.move_after.is_some() => {
+                    let (parent, name) = split_parent(remote_path);
+                    if let Err(err) = self.client.remove(parent, &[name.to_string()]).await {
+                        warn!("Could not remove {} after it failed verification: {}", remote_path, err);
+                    }
+                    return Err(err);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        if let Some(dir) = &self.options.move_after {
+            moved = self.move_after(remote_path, dir).await?;
+            result.path = &moved;
+        }
+        self.emit(&result)
+    }
+
+    /// Move the finished upload at `remote_path` into the remote directory `dir`, given
+    /// as a path on the server, returning where it is now.
+    async fn move_after(&self, remote_path: &str, dir: &str) -> Result<String> {
+        let dir = plain_path(self.opts, dir);
+        if self.options.mkdir {
+            self.client.ensure_dir(&dir).await?;
+        }
+        let (parent, name) = split_parent(remote_path);
+        self.client.move_files(parent, &dir, &[name.to_string()]).await?;
+        let moved = join(&dir, name);
+        if !self.opts.json {
+            self.say(format_args!("Moved {} to {}", remote_path, moved));
+        }
+        Ok(moved)
+    }
+
+    /// Print `result` in `--json` mode, or the file's URL with `--quiet-success`; the
+    /// text output has been written already.
+    fn emit(&self, result: &UploadResult) -> Result<Outcome> {
+        audit::record_file(result.path, result.status, result.size, None);
+        if self.opts.json {
+            print_json(result)?;
+        } else if self.options.quiet_success {
+            println!("{}{}", self.client.base_url(), encode_path(result.path));
+        }
+        Ok(Outcome::from(result))
+    }
+
+    /// Print a line of text output: on stdout, unless `--quiet-success` keeps stdout
+    /// for the URLs of uploaded files.
+    fn say(&self, line: std::fmt::Arguments) {
+        if self.options.quiet_success {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    }
+
+    /// Compare the hash of what was sent with the one the server reports, of the
+    /// strongest kind it reports among those hashed, returning the hash when it could
+    /// be checked.
+    async fn verify(&self, remote_path: &str, hasher: &StreamHasher) -> Result<Option<String>> {
+        let hashes = self.client.get(remote_path).await?.hash_info.unwrap_or_default();
+        let Some((kind, remote)) = hashes.strongest(hasher.kinds()) else {
+            let reported = hashes.reported();
+            if reported.is_empty() {
+                warn!("Not verifying {}: the server reports no hash for it", remote_path);
+                return Ok(None);
+            }
+            let hashed: Vec<_> = hasher.kinds().iter().map(|kind| kind.key()).collect();
+            return Err(AlistError::Config(format!(
+                "cannot verify {}: the server reports {} hashes for it but no {}",
+                remote_path,
+                reported.join(", "),
+                hashed.join(" or ")
+            )));
+        };
+        debug!("Verifying {} by its {} hash", remote_path, kind.key());
+        let local = hasher.hex_of(kind).expect("a kind that was hashed");
+        if !remote.eq_ignore_ascii_case(&local) {
+            return Err(AlistError::HashMismatch {
+                path: remote_path.to_string(),
+                local,
+                remote: remote.to_string(),
+            });
+        }
+        if !self.opts.json {
+            eprintln!("Verified {} ({} {})", remote_path, kind.key(), local);
+        }
+        Ok(Some(local))
+    }
+}
+
+/// A name for the temporary upload of `name` that no other upload picks: hidden,
+/// unique to this process and moment, and ending in `.part`.
+fn temporary_name(name: &str) -> String {
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.subsec_nanos());
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!(".{}.{:x}-{:x}{:x}.part", name, std::process::id(), nanos, count)
+}
+
+/// Expand the glob patterns among `args`, keeping literal paths as they are. A
+/// pattern matching nothing is an error unless `allow_empty` is set.
+fn expand_sources(args: &[String], allow_empty: bool) -> Result<Vec<PathBuf>> {
+    let mut sources = Vec::new();
+    for arg in args {
+        if !is_glob(arg) {
+            sources.push(PathBuf::from(arg));
+            continue;
+        }
+        let matches = glob::glob(arg)
+            .map_err(|err| AlistError::Config(format!("invalid pattern {}: {}", arg, err)))?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|err| AlistError::Io(io::Error::new(err.error().kind(), err)))?;
+        if matches.is_empty() && !allow_empty {
+            return Err(AlistError::Config(format!("no local files match {}", arg)));
+        }
+        sources.extend(matches);
+    }
+    Ok(sources)
+}
@@ -0,0 +1,76 @@
+//! Comparing a remote file with a local one without transferring either.
+
+use alist_cli::hash::hash_file;
+use alist_cli::{AlistError, Result};
+use serde::Serialize;
+
+use super::upload::local_metadata;
+use super::{connect, print_json};
+use crate::cli::{GlobalOpts, VerifyArgs};
+
+/// `--json` result of `verify`.
+#[derive(Serialize)]
+struct VerifyResult<'a> {
+    status: &'static str,
+    path: &'a str,
+    size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hash_kind: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hash: Option<&'a str>,
+}
+
+/// Compare the size the server reports for a remote file with the local file's, and
+/// then the hash, unless `--size-only`. A difference is an error.
+pub async fn cmd_verify(opts: &GlobalOpts, args: &VerifyArgs) -> Result<()> {
+    let (client, remote_path) = connect(opts, &args.alist_url).await?;
+    let info = client.get(&remote_path).await?;
+    if info.is_dir {
+        return Err(AlistError::Config(format!("{} is a directory", remote_path)));
+    }
+    let size = local_metadata(&args.local_file).await?.len();
+    if info.size != size {
+        return Err(AlistError::SizeMismatch {
+            path: remote_path,
+            local: size,
+            remote: info.size,
+        });
+    }
+
+    let mut checked = None;
+    if !args.size_only {
+        let hashes = info.hash_info.unwrap_or_default();
+        let Some((kind, remote)) = hashes.pick(args.hash) else {
+            let kind = args.hash.map_or("md5, sha1 or sha256", |kind| kind.key());
+            return Err(AlistError::Config(format!(
+                "the server reports no {} hash for {}; use --size-only to compare sizes alone",
+                kind, remote_path
+            )));
+        };
+        let local = hash_file(&args.local_file, kind).await?;
+        if !local.eq_ignore_ascii_case(&remote) {
+            return Err(AlistError::HashMismatch {
+                path: remote_path,
+                local,
+                remote,
+            });
+        }
+        checked = Some((kind.key(), local));
+    }
+
+    if opts.json {
+        return print_json(&VerifyResult {
+            status: "ok",
+            path: &remote_path,
+            size,
+            hash_kind: checked.as_ref().map(|(kind, _)| *kind),
+            hash: checked.as_ref().map(|(_, hash)| hash.as_str()),
+        });
+    }
+    let local = args.local_file.display();
+    match &checked {
+        Some((kind, hash)) => println!("{} matches {} ({} {})", local, remote_path, kind, hash),
+        None => println!("{} matches {} ({} bytes)", local, remote_path, size),
+    }
+    Ok(())
+}
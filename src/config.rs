@@ -0,0 +1,73 @@
+//! Server profiles read from `~/.config/alist-cli/config.toml`.
+//!
+//! ```toml
+//! [profiles.home]
+//! url = "https://alist.example.com"
+//! username = "admin"
+//! password = "secret"
+//! default_dir = "/media/movies"
+//! ```
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::error::{AlistError, Result};
+
+#[derive(Deserialize, Debug, Default)]
+pub struct Config {
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+}
+
+/// Connection details for one Alist server. Every field is optional so a
+/// profile can leave, say, the password to be prompted for.
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct Profile {
+    pub url: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Remote directory that relative plain paths are resolved against, like `--cwd`.
+    pub default_dir: Option<String>,
+}
+
+impl Config {
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("alist-cli").join("config.toml"))
+    }
+
+    /// Load the config from `path`, or from [`Config::default_path`] when `None`.
+    ///
+    /// An explicitly given file must exist; a missing default file is treated as empty.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let (path, explicit) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match Self::default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == ErrorKind::NotFound && !explicit => return Ok(Config::default()),
+            Err(err) => return Err(err.into()),
+        };
+        toml::from_str(&text).map_err(|err| AlistError::Config(format!("{}: {}", path.display(), err)))
+    }
+
+    /// Names of all profiles, in alphabetical order.
+    pub fn profile_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    pub fn profile(&self, name: &str) -> Result<&Profile> {
+        self.profiles
+            .get(name)
+            .ok_or_else(|| AlistError::Config(format!("no profile named `{}`", name)))
+    }
+}
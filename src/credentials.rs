@@ -0,0 +1,38 @@
+//! Account passwords kept in the operating system's secret store, such as the macOS
+//! Keychain, the Windows Credential Manager or the Secret Service on Linux, instead
+//! of the config file.
+
+use keyring::Entry;
+use tracing::warn;
+
+use crate::error::{AlistError, Result};
+
+/// Service the passwords are stored under.
+const SERVICE: &str = "alist-cli";
+
+/// The entry for `username` on the server at `base_url`.
+fn entry(base_url: &str, username: &str) -> keyring::Result<Entry> {
+    Entry::new(SERVICE, &format!("{}@{}", username, base_url))
+}
+
+/// The password stored for `username` on `base_url`, if any. A secret store that
+/// cannot be reached is warned about and treated as holding none.
+pub fn load_password(base_url: &str, username: &str) -> Option<String> {
+    match entry(base_url, username).and_then(|entry| entry.get_password()) {
+        Ok(password) => Some(password),
+        Err(keyring::Error::NoEntry) => None,
+        Err(err) => {
+            warn!("Could not read from the system keyring: {}", err);
+            None
+        }
+    }
+}
+
+/// Store `password` for `username` on `base_url`, replacing any stored before.
+pub fn save_password(base_url: &str, username: &str, password: &str) -> Result<()> {
+    entry(base_url, username)
+        .and_then(|entry| entry.set_password(password))
+        .map_err(|err| {
+            AlistError::Config(format!("cannot store the password in the system keyring: {}", err))
+        })
+}
@@ -0,0 +1,249 @@
+//! Client-side encryption of uploads with a passphrase, so the server and the storage
+//! behind it only ever see ciphertext.
+//!
+//! An encrypted file starts with a [`HEADER_LEN`]-byte header: a magic number, the
+//! format version, a random salt and a random nonce prefix. The data follows in
+//! segments of [`SEGMENT_SIZE`] bytes, each sealed with AES-256-GCM on its own, the
+//! last one shorter and possibly empty. A segment's nonce is the prefix, its index and
+//! whether it is the last, so segments cannot be reordered, dropped or cut off without
+//! decryption failing. The key is derived from the passphrase and the salt with
+//! PBKDF2-HMAC-SHA256.
+
+use futures_util::stream::{self, Stream, StreamExt};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::pbkdf2;
+use ring::rand::{SecureRandom, SystemRandom};
+use std::io;
+use std::mem;
+use std::num::NonZeroU32;
+use std::sync::Arc;
+
+/// Extension added to the remote name of an encrypted file.
+pub const EXTENSION: &str = "enc";
+
+const MAGIC: &[u8; 8] = b"ALISTENC";
+const VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+/// The part of each nonce that is the same for the whole file; the rest is the
+/// segment's index and the last-segment flag.
+const PREFIX_LEN: usize = NONCE_LEN - 5;
+/// Length of the header an encrypted file starts with.
+pub const HEADER_LEN: usize = MAGIC.len() + 1 + SALT_LEN + PREFIX_LEN;
+/// Bytes of plaintext sealed together.
+pub const SEGMENT_SIZE: usize = 64 * 1024;
+const TAG_LEN: usize = 16;
+const ITERATIONS: u32 = 600_000;
+
+/// A passphrase to derive keys from, kept out of `Debug` output.
+#[derive(Clone)]
+pub struct Passphrase(Arc<str>);
+
+impl Passphrase {
+    pub fn new(passphrase: impl Into<Arc<str>>) -> Self {
+        Passphrase(passphrase.into())
+    }
+}
+
+impl std::fmt::Debug for Passphrase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Passphrase([redacted])")
+    }
+}
+
+/// The size of a file of `size` bytes once encrypted.
+pub fn encrypted_size(size: u64) -> u64 {
+    let segments = size / SEGMENT_SIZE as u64 + 1;
+    HEADER_LEN as u64 + size + segments * TAG_LEN as u64
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// The key of one file, and the index of the next segment to seal or open.
+struct Segments {
+    key: LessSafeKey,
+    header: [u8; HEADER_LEN],
+    index: u32,
+}
+
+impl Segments {
+    fn new(passphrase: &Passphrase, header: [u8; HEADER_LEN]) -> Self {
+        let salt = &header[MAGIC.len() + 1..][..SALT_LEN];
+        let iterations = NonZeroU32::new(ITERATIONS).expect("iterations are not zero");
+        let mut key = [0; 32];
+        pbkdf2::derive(pbkdf2::PBKDF2_HMAC_SHA256, iterations, salt, passphrase.0.as_bytes(), &mut key);
+        let key = UnboundKey::new(&AES_256_GCM, &key).expect("a key of 256 bits");
+        Segments {
+            key: LessSafeKey::new(key),
+            header,
+            index: 0,
+        }
+    }
+
+    fn next_nonce(&mut self, last: bool) -> io::Result<Nonce> {
+        let mut nonce = [0; NONCE_LEN];
+        nonce[..PREFIX_LEN].copy_from_slice(&self.header[HEADER_LEN - PREFIX_LEN..]);
+        nonce[PREFIX_LEN..NONCE_LEN - 1].copy_from_slice(&self.index.to_be_bytes());
+        nonce[NONCE_LEN - 1] = u8::from(last);
+        self.index = self
+            .index
+            .checked_add(1)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "too much data for one file"))?;
+        Ok(Nonce::assume_unique_for_key(nonce))
+    }
+
+    fn seal(&mut self, mut segment: Vec<u8>, last: bool) -> io::Result<Vec<u8>> {
+        let nonce = self.next_nonce(last)?;
+        self.key
+            .seal_in_place_append_tag(nonce, Aad::from(&self.header), &mut segment)
+            .map_err(|_| io::Error::other("could not encrypt"))?;
+        Ok(segment)
+    }
+
+    fn open(&mut self, mut segment: Vec<u8>, last: bool) -> io::Result<Vec<u8>> {
+        let nonce = self.next_nonce(last)?;
+        let len = self
+            .key
+            .open_in_place(nonce, Aad::from(&self.header), &mut segment)
+            .map_err(|_| invalid("cannot decrypt: wrong passphrase, or the file is damaged or cut off"))?
+            .len();
+        segment.truncate(len);
+        Ok(segment)
+    }
+}
+
+/// Encrypt what `stream` yields with a key derived from `passphrase` and a fresh salt,
+/// yielding the header and then one sealed segment at a time.
+pub fn encrypt<S, B, E>(
+    stream: S,
+    passphrase: &Passphrase,
+) -> io::Result<impl Stream<Item = Result<Vec<u8>, E>>>
+where
+    S: Stream<Item = Result<B, E>>,
+    B: AsRef<[u8]>,
+    E: From<io::Error>,
+{
+    let mut header = [0; HEADER_LEN];
+    header[..MAGIC.len()].copy_from_slice(MAGIC);
+    header[MAGIC.len()] = VERSION;
+    SystemRandom::new()
+        .fill(&mut header[MAGIC.len() + 1..])
+        .map_err(|_| io::Error::other("no random numbers to encrypt with"))?;
+    let segments = Segments::new(passphrase, header);
+
+    let sealed = stream::unfold(
+        Some((Box::pin(stream), Vec::new(), segments)),
+        |state| async move {
+            let (mut stream, mut buf, mut segments) = state?;
+            loop {
+                if buf.len() >= SEGMENT_SIZE {
+                    let rest = buf.split_off(SEGMENT_SIZE);
+                    let sealed = segments.seal(mem::replace(&mut buf, rest), false);
+                    return Some((sealed.map_err(E::from), Some((stream, buf, segments))));
+                }
+                match stream.next().await {
+                    Some(Ok(chunk)) => buf.extend_from_slice(chunk.as_ref()),
+                    Some(Err(err)) => return Some((Err(err), None)),
+                    None => return Some((segments.seal(buf, true).map_err(E::from), None)),
+                }
+            }
+        },
+    );
+    Ok(stream::once(async move { Ok(header.to_vec()) }).chain(sealed))
+}
+
+/// Decrypt a file [`encrypt`] made as `stream` yields it, one segment at a time.
+/// Anything else, a wrong passphrase and a file that was altered or cut short fail
+/// with [`io::ErrorKind::InvalidData`].
+pub fn decrypt<S, B, E>(stream: S, passphrase: Passphrase) -> impl Stream<Item = Result<Vec<u8>, E>>
+where
+    S: Stream<Item = Result<B, E>>,
+    B: AsRef<[u8]>,
+    E: From<io::Error>,
+{
+    let not_encrypted = || invalid("not a file encrypted by alist-cli");
+    stream::unfold(
+        Some((Box::pin(stream), Vec::new(), None::<Segments>)),
+        move |state| {
+            let passphrase = passphrase.clone();
+            async move {
+                let (mut stream, mut buf, mut segments) = state?;
+                loop {
+                    if segments.is_none() && buf.len() >= HEADER_LEN {
+                        let header: [u8; HEADER_LEN] = buf[..HEADER_LEN].try_into().expect("a whole header");
+                        if header[..MAGIC.len()] != MAGIC[..] || header[MAGIC.len()] != VERSION {
+                            return Some((Err(not_encrypted().into()), None));
+                        }
+                        buf.drain(..HEADER_LEN);
+                        segments = Some(Segments::new(&passphrase, header));
+                    }
+                    // Only once more follows can a full segment be told from the last one.
+                    if let Some(open) = segments.as_mut().filter(|_| buf.len() > SEGMENT_SIZE + TAG_LEN) {
+                        let rest = buf.split_off(SEGMENT_SIZE + TAG_LEN);
+                        return match open.open(mem::replace(&mut buf, rest), false) {
+                            Ok(plain) => Some((Ok(plain), Some((stream, buf, segments)))),
+                            Err(err) => Some((Err(err.into()), None)),
+                        };
+                    }
+                    match stream.next().await {
+                        Some(Ok(chunk)) => buf.extend_from_slice(chunk.as_ref()),
+                        Some(Err(err)) => return Some((Err(err), None)),
+                        None => {
+                            let plain = match segments.as_mut() {
+                                Some(open) => open.open(buf, true),
+                                None => Err(not_encrypted()),
+                            };
+                            return Some((plain.map_err(E::from), None));
+                        }
+                    }
+                }
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn collect(stream: impl Stream<Item = io::Result<Vec<u8>>>) -> io::Result<Vec<u8>> {
+        let chunks: Vec<_> = stream.collect().await;
+        Ok(chunks.into_iter().collect::<io::Result<Vec<_>>>()?.concat())
+    }
+
+    fn chunks(data: &[u8]) -> impl Stream<Item = io::Result<Vec<u8>>> {
+        let chunks: Vec<_> = data.chunks(10_000).map(|chunk| Ok(chunk.to_vec())).collect();
+        stream::iter(chunks)
+    }
+
+    #[tokio::test]
+    async fn encrypted_files_decrypt_to_what_went_in() {
+        let passphrase = Passphrase::new("correct horse");
+        for size in [0, 5, SEGMENT_SIZE, 2 * SEGMENT_SIZE + 1] {
+            let data: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+            let sealed = collect(encrypt(chunks(&data), &passphrase).unwrap()).await.unwrap();
+            assert_eq!(sealed.len() as u64, encrypted_size(size as u64));
+            if size > 0 {
+                let head = size.min(64);
+                assert_ne!(&sealed[HEADER_LEN..HEADER_LEN + head], &data[..head]);
+            }
+            let plain = collect(decrypt(chunks(&sealed), passphrase.clone())).await.unwrap();
+            assert_eq!(plain, data);
+        }
+    }
+
+    #[tokio::test]
+    async fn a_wrong_passphrase_or_a_cut_off_file_fails() {
+        let data = vec![7; SEGMENT_SIZE + 100];
+        let sealed = collect(encrypt(chunks(&data), &Passphrase::new("right")).unwrap()).await.unwrap();
+
+        let wrong = collect(decrypt(chunks(&sealed), Passphrase::new("wrong"))).await.unwrap_err();
+        assert_eq!(wrong.kind(), io::ErrorKind::InvalidData);
+        let cut = &sealed[..HEADER_LEN + SEGMENT_SIZE + TAG_LEN];
+        let cut = collect(decrypt(chunks(cut), Passphrase::new("right"))).await.unwrap_err();
+        assert_eq!(cut.kind(), io::ErrorKind::InvalidData);
+        let plain = collect(decrypt(chunks(b"plain text"), Passphrase::new("right"))).await.unwrap_err();
+        assert_eq!(plain.kind(), io::ErrorKind::InvalidData);
+    }
+}
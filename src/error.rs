@@ -0,0 +1,226 @@
+use std::time::Duration;
+use thiserror::Error;
+
+/// Errors returned by [`AlistClient`](crate::AlistClient).
+#[derive(Debug, Error)]
+pub enum AlistError {
+    /// The request could not be sent or the response could not be read.
+    #[error("HTTP request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    /// The server replied with a body that is not the JSON we expected.
+    #[error("failed to parse response: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// A local file could not be read or written.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// Logging in failed, or a request was made without a valid token.
+    #[error("authentication failed: {message}")]
+    Auth { message: String },
+
+    /// The account has two-factor authentication enabled and the login carried no
+    /// valid code.
+    #[error("two-factor code required: {message}")]
+    OtpRequired { message: String },
+
+    /// The server asked for requests to slow down with HTTP 429 or 503, saying how long
+    /// to wait if its `Retry-After` header could be read.
+    #[error("server is busy or rate limiting requests (HTTP {status})")]
+    Throttled { status: u16, retry_after: Option<Duration> },
+
+    /// An HTTP error of a request the server, or a proxy in front of it, gave an ID,
+    /// quoted for bug reports. Check the error itself with [`AlistError::root`].
+    #[error("{source} [request ID {request_id}]")]
+    Request {
+        request_id: String,
+        source: Box<AlistError>,
+    },
+
+    /// The server answered with a non-success `code`.
+    #[error("{message} (code {code})")]
+    Api { code: u16, message: String },
+
+    /// A URL given on the command line could not be parsed.
+    #[error("invalid URL: {0}")]
+    InvalidUrl(String),
+
+    /// The config file or command line options are inconsistent.
+    #[error("{0}")]
+    Config(String),
+
+    /// A remote path or name that cannot be sent to the server.
+    #[error("invalid remote path: {0}")]
+    InvalidPath(String),
+
+    /// Nothing exists at a remote path that was required to.
+    #[error("remote path not found: {0}")]
+    NotFound(String),
+
+    /// The target of an upload exists and overwriting was not allowed.
+    #[error("remote path already exists: {0}")]
+    AlreadyExists(String),
+
+    /// An uploaded file's hash on the server differs from the local one.
+    #[error("hash mismatch for {path}: local {local}, remote {remote}")]
+    HashMismatch {
+        path: String,
+        local: String,
+        remote: String,
+    },
+
+    /// A remote file's size differs from that of the local file it should match.
+    #[error("size mismatch for {path}: local {local} bytes, remote {remote} bytes")]
+    SizeMismatch { path: String, local: u64, remote: u64 },
+
+    /// Some items of a bulk operation failed; each failure has been reported already.
+    #[error("{failed} of {total} operations failed")]
+    PartialFailure { failed: usize, total: usize },
+
+    /// A background task was still running when the time allowed for it ran out.
+    #[error("task {id} did not finish within {secs} seconds")]
+    TaskTimeout { id: String, secs: u64 },
+
+    /// The user interrupted the command with Ctrl-C.
+    #[error("aborted by user")]
+    Aborted,
+}
+
+impl AlistError {
+    /// The error without the request ID of [`AlistError::Request`].
+    pub fn root(&self) -> &AlistError {
+        match self {
+            AlistError::Request { source, .. } => source.root(),
+            err => err,
+        }
+    }
+
+    /// Quote `request_id`, when the response had one, in an error about it: in the
+    /// message of an [`AlistError::Api`], or around an HTTP error as
+    /// [`AlistError::Request`].
+    pub(crate) fn with_request_id(self, request_id: Option<&str>) -> AlistError {
+        let Some(id) = request_id else {
+            return self;
+        };
+        match self {
+            AlistError::Api { code, message } => AlistError::Api {
+                code,
+                message: format!("{} [request ID {}]", message, id),
+            },
+            err @ (AlistError::Http(_) | AlistError::Throttled { .. }) => AlistError::Request {
+                request_id: id.to_string(),
+                source: Box::new(err),
+            },
+            err => err,
+        }
+    }
+
+    /// Whether the server reported that the requested object does not exist.
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, AlistError::Api { message, .. } if message.contains("not found"))
+    }
+}
+
+pub type Result<T> = std::result::Result<T, AlistError>;
+
+/// The exit status of `alist-cli` for each kind of outcome. Scripts may rely on these
+/// numbers; they only change with a major version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    Success = 0,
+    /// Bad options or arguments, including an invalid URL or remote path.
+    Usage = 2,
+    /// Logging in failed or the token was rejected.
+    Auth = 3,
+    /// The server could not be reached, or kept asking to slow down.
+    Network = 4,
+    /// A local file could not be read or written.
+    Io = 5,
+    /// The server refused the request, or what it holds is missing or does not match.
+    ApiError = 6,
+    /// Some items of a bulk operation failed.
+    PartialFailure = 7,
+    /// Interrupted with Ctrl-C, like a shell's 128 + SIGINT.
+    Aborted = 130,
+}
+
+impl ExitCode {
+    /// The exit status for the outcome of a command.
+    pub fn of<T>(result: &Result<T>) -> Self {
+        result.as_ref().err().map_or(ExitCode::Success, ExitCode::from)
+    }
+
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+}
+
+impl From<&AlistError> for ExitCode {
+    fn from(err: &AlistError) -> Self {
+        match err.root() {
+            AlistError::InvalidUrl(_) | AlistError::InvalidPath(_) | AlistError::Config(_) => ExitCode::Usage,
+            AlistError::Auth { .. } | AlistError::OtpRequired { .. } => ExitCode::Auth,
+            AlistError::Http(_) | AlistError::Throttled { .. } => ExitCode::Network,
+            AlistError::Io(_) => ExitCode::Io,
+            AlistError::Api { .. }
+            | AlistError::Json(_)
+            | AlistError::NotFound(_)
+            | AlistError::AlreadyExists(_)
+            | AlistError::HashMismatch { .. }
+            | AlistError::SizeMismatch { .. }
+            | AlistError::TaskTimeout { .. } => ExitCode::ApiError,
+            AlistError::PartialFailure { .. } => ExitCode::PartialFailure,
+            AlistError::Aborted => ExitCode::Aborted,
+            AlistError::Request { .. } => unreachable!("the root of an error is no request"),
+        }
+    }
+}
+
+impl From<ExitCode> for std::process::ExitCode {
+    fn from(code: ExitCode) -> Self {
+        std::process::ExitCode::from(code as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_map_to_the_documented_exit_codes() {
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "gone");
+        let json = serde_json::from_str::<u8>("x").unwrap_err();
+        let cases = [
+            (AlistError::Config("bad".to_string()), 2),
+            (AlistError::InvalidUrl("ftp://".to_string()), 2),
+            (AlistError::InvalidPath("a\0".to_string()), 2),
+            (AlistError::Auth { message: "nope".to_string() }, 3),
+            (AlistError::OtpRequired { message: "code".to_string() }, 3),
+            (AlistError::Throttled { status: 429, retry_after: None }, 4),
+            (AlistError::Io(io), 5),
+            (AlistError::Api { code: 500, message: "failed".to_string() }, 6),
+            (AlistError::Json(json), 6),
+            (AlistError::NotFound("/a".to_string()), 6),
+            (AlistError::AlreadyExists("/a".to_string()), 6),
+            (
+                AlistError::HashMismatch {
+                    path: "/a".to_string(),
+                    local: "1".to_string(),
+                    remote: "2".to_string(),
+                },
+                6,
+            ),
+            (AlistError::SizeMismatch { path: "/a".to_string(), local: 1, remote: 2 }, 6),
+            (AlistError::TaskTimeout { id: "1".to_string(), secs: 5 }, 6),
+            (AlistError::PartialFailure { failed: 1, total: 2 }, 7),
+            (AlistError::Aborted, 130),
+        ];
+        for (err, code) in cases {
+            assert_eq!(ExitCode::from(&err).code(), code, "{:?}", err);
+            let identified = err.with_request_id(Some("abc"));
+            assert_eq!(ExitCode::from(&identified).code(), code, "{:?}", identified);
+        }
+        assert_eq!(ExitCode::of(&Ok(())), ExitCode::Success);
+    }
+}
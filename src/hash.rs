@@ -0,0 +1,209 @@
+//! Hashing of transferred data, for verifying uploads against the server.
+
+use futures_util::stream::{Stream, StreamExt};
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+
+/// A hash algorithm Alist can report for a stored file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HashKind {
+    #[default]
+    Md5,
+    Sha1,
+    Sha256,
+}
+
+impl HashKind {
+    /// Every kind, weakest first.
+    pub const ALL: [HashKind; 3] = [HashKind::Md5, HashKind::Sha1, HashKind::Sha256];
+
+    /// The key of this hash in the `hash_info` map of `fs/get`.
+    pub fn key(self) -> &'static str {
+        match self {
+            HashKind::Md5 => "md5",
+            HashKind::Sha1 => "sha1",
+            HashKind::Sha256 => "sha256",
+        }
+    }
+
+    /// The header `fs/put` and `fs/form` take this hash of the upload in.
+    pub fn header(self) -> &'static str {
+        match self {
+            HashKind::Md5 => "X-File-Md5",
+            HashKind::Sha1 => "X-File-Sha1",
+            HashKind::Sha256 => "X-File-Sha256",
+        }
+    }
+}
+
+/// The `hash_info` map of `fs/get` and `fs/list`: the hashes the storage driver
+/// reports for a file, keyed by algorithm. Drivers may report kinds alist-cli cannot
+/// compute, such as `gcid`, and empty hashes, which count as missing.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(transparent)]
+pub struct HashInfo(pub HashMap<String, String>);
+
+impl HashInfo {
+    pub fn get(&self, kind: HashKind) -> Option<&str> {
+        self.0.get(kind.key()).map(String::as_str).filter(|hash| !hash.is_empty())
+    }
+
+    /// The strongest of `kinds` the server reports, with its hash.
+    pub fn strongest(&self, kinds: &[HashKind]) -> Option<(HashKind, &str)> {
+        HashKind::ALL
+            .iter()
+            .rev()
+            .filter(|kind| kinds.contains(kind))
+            .find_map(|&kind| self.get(kind).map(|hash| (kind, hash)))
+    }
+
+    /// The hash of `kind`, or without a `kind` the strongest the server reports.
+    pub fn pick(&self, kind: Option<HashKind>) -> Option<(HashKind, String)> {
+        let kinds = kind.map_or(HashKind::ALL.to_vec(), |kind| vec![kind]);
+        self.strongest(&kinds).map(|(kind, hash)| (kind, hash.to_string()))
+    }
+
+    /// The algorithms reported with a hash, sorted, for messages.
+    pub fn reported(&self) -> Vec<&str> {
+        let mut keys: Vec<_> = self
+            .0
+            .iter()
+            .filter(|(_, hash)| !hash.is_empty())
+            .map(|(key, _)| key.as_str())
+            .collect();
+        keys.sort_unstable();
+        keys
+    }
+}
+
+impl FromStr for HashKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "md5" => Ok(HashKind::Md5),
+            "sha1" => Ok(HashKind::Sha1),
+            "sha256" => Ok(HashKind::Sha256),
+            _ => Err(format!("unknown hash `{}`, expected `md5`, `sha1` or `sha256`", s)),
+        }
+    }
+}
+
+enum State {
+    Md5(Md5),
+    Sha1(Sha1),
+    Sha256(Sha256),
+}
+
+impl State {
+    fn new(kind: HashKind) -> Self {
+        match kind {
+            HashKind::Md5 => State::Md5(Md5::new()),
+            HashKind::Sha1 => State::Sha1(Sha1::new()),
+            HashKind::Sha256 => State::Sha256(Sha256::new()),
+        }
+    }
+}
+
+/// A running hash of one or more kinds at once that can be fed from inside a
+/// request body stream and read once the transfer is done. Clones share the same state.
+#[derive(Clone)]
+pub struct StreamHasher {
+    kinds: Vec<HashKind>,
+    states: Arc<Mutex<Vec<State>>>,
+}
+
+impl StreamHasher {
+    pub fn new(kind: HashKind) -> Self {
+        Self::of(&[kind])
+    }
+
+    /// A hasher of each of `kinds`, for when which the server reports is only known
+    /// after the transfer.
+    pub fn of(kinds: &[HashKind]) -> Self {
+        StreamHasher {
+            kinds: kinds.to_vec(),
+            states: Arc::new(Mutex::new(kinds.iter().map(|&kind| State::new(kind)).collect())),
+        }
+    }
+
+    pub fn kinds(&self) -> &[HashKind] {
+        &self.kinds
+    }
+
+    /// Forget everything hashed so far, e.g. before retrying a transfer.
+    pub fn reset(&self) {
+        let fresh = self.kinds.iter().map(|&kind| State::new(kind)).collect();
+        *self.states.lock().expect("hasher lock") = fresh;
+    }
+
+    pub fn update(&self, data: &[u8]) {
+        for state in self.states.lock().expect("hasher lock").iter_mut() {
+            match state {
+                State::Md5(hasher) => hasher.update(data),
+                State::Sha1(hasher) => hasher.update(data),
+                State::Sha256(hasher) => hasher.update(data),
+            }
+        }
+    }
+
+    /// The lowercase hex digest of everything hashed so far, of the first kind.
+    pub fn hex(&self) -> String {
+        self.hex_of(self.kinds[0]).expect("hasher of its first kind")
+    }
+
+    /// The lowercase hex digest of everything hashed so far of `kind`, if it is
+    /// among the kinds hashed.
+    pub fn hex_of(&self, kind: HashKind) -> Option<String> {
+        let index = self.kinds.iter().position(|&hashed| hashed == kind)?;
+        let digest = match &self.states.lock().expect("hasher lock")[index] {
+            State::Md5(hasher) => hasher.clone().finalize().to_vec(),
+            State::Sha1(hasher) => hasher.clone().finalize().to_vec(),
+            State::Sha256(hasher) => hasher.clone().finalize().to_vec(),
+        };
+        Some(digest.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+}
+
+impl std::fmt::Debug for StreamHasher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StreamHasher").field("kinds", &self.kinds).finish()
+    }
+}
+
+/// Hash the contents of a local file.
+pub async fn hash_file(path: &Path, kind: HashKind) -> io::Result<String> {
+    let mut file = File::open(path).await?;
+    let hasher = StreamHasher::new(kind);
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buf).await?;
+        if read == 0 {
+            return Ok(hasher.hex());
+        }
+        hasher.update(&buf[..read]);
+    }
+}
+
+/// Feed every chunk `stream` yields into `hasher`, if there is one.
+pub fn track<S, B, E>(stream: S, hasher: Option<StreamHasher>) -> impl Stream<Item = Result<B, E>>
+where
+    S: Stream<Item = Result<B, E>>,
+    B: AsRef<[u8]>,
+{
+    stream.map(move |chunk| {
+        if let (Ok(bytes), Some(hasher)) = (&chunk, &hasher) {
+            hasher.update(bytes.as_ref());
+        }
+        chunk
+    })
+}
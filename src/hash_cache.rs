@@ -0,0 +1,86 @@
+//! Hashes of local files remembered between runs, so `sync --checksum` only rehashes
+//! files that changed.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::error::Result;
+use crate::hash::{hash_file, HashKind};
+
+/// The hashes of one file, valid while its size and modification time are unchanged.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct CachedHashes {
+    size: u64,
+    /// Modification time in seconds and nanoseconds since the Unix epoch.
+    mtime: (u64, u32),
+    /// Hex digests keyed by [`HashKind::key`].
+    hashes: BTreeMap<String, String>,
+}
+
+/// Hashes of the files below one directory, kept in a sidecar file in that directory
+/// and keyed by path relative to it.
+#[derive(Debug)]
+pub struct HashCache {
+    root: PathBuf,
+    entries: HashMap<String, CachedHashes>,
+    /// The entries looked up during this run; only these are saved.
+    used: HashMap<String, CachedHashes>,
+}
+
+impl HashCache {
+    /// Name of the sidecar file, which `sync` does not upload.
+    pub const FILE_NAME: &'static str = ".alist-cli-hashes.json";
+
+    /// Load the cache for `root`, treating a missing or unreadable file as empty.
+    pub fn load(root: &Path) -> Self {
+        let entries = fs::read(root.join(Self::FILE_NAME))
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        HashCache {
+            root: root.to_path_buf(),
+            entries,
+            used: HashMap::new(),
+        }
+    }
+
+    /// The `kind` hash of the file at `relative` below the root, from the cache if the
+    /// file has not changed since it was hashed.
+    pub async fn hash(&mut self, relative: &Path, kind: HashKind) -> Result<String> {
+        let path = self.root.join(relative);
+        let metadata = tokio::fs::metadata(&path).await?;
+        let since = metadata.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mtime = (since.as_secs(), since.subsec_nanos());
+        let key = relative.to_string_lossy().into_owned();
+
+        let mut entry = match self.used.remove(&key).or_else(|| self.entries.remove(&key)) {
+            Some(entry) if entry.size == metadata.len() && entry.mtime == mtime => entry,
+            _ => CachedHashes {
+                size: metadata.len(),
+                mtime,
+                hashes: BTreeMap::new(),
+            },
+        };
+        let hash = match entry.hashes.get(kind.key()) {
+            Some(hash) => hash.clone(),
+            None => {
+                let hash = hash_file(&path, kind).await?;
+                entry.hashes.insert(kind.key().to_string(), hash.clone());
+                hash
+            }
+        };
+        self.used.insert(key, entry);
+        Ok(hash)
+    }
+
+    /// Write the entries used during this run back to the sidecar file, dropping
+    /// those of files that were not looked at.
+    pub fn save(&self) -> Result<()> {
+        let data = serde_json::to_vec_pretty(&self.used)?;
+        fs::write(self.root.join(Self::FILE_NAME), data)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,20 @@
+//! Client library for the [Alist](https://alist.nn.ci) HTTP API.
+
+pub mod checkpoint;
+pub mod client;
+pub mod config;
+pub mod credentials;
+pub mod crypt;
+pub mod error;
+pub mod hash;
+pub mod hash_cache;
+pub mod model;
+pub mod part_manifest;
+pub mod path;
+pub mod progress;
+pub mod retry;
+pub mod throttle;
+pub mod token_cache;
+
+pub use client::AlistClient;
+pub use error::{AlistError, ExitCode, Result};
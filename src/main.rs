@@ -1,150 +1,161 @@
-use reqwest::{
-    header::{HeaderMap, AUTHORIZATION},
-    Client,
-};
-use serde::{Deserialize, Serialize};
-use serde_json;
-use std::env;
+use alist_cli::{AlistError, ExitCode, Result};
+use clap::{CommandFactory, Parser};
+use serde::Serialize;
 use std::process;
-use url::Url;
-use tokio::fs::File;
-use tokio_util::codec::{BytesCodec, FramedRead};
-use futures_util::stream::StreamExt; // 用于处理异步流
+use std::time::SystemTime;
+use tracing::warn;
+use tracing_subscriber::EnvFilter;
 
-#[derive(Serialize, Deserialize)]
-struct LoginRequest {
-    username: String,
-    password: String,
-}
+mod cli;
+mod commands;
 
-#[derive(Serialize, Deserialize, Debug)]
-struct LoginResponse {
-    code: u16,
-    message: String,
-    data: Option<LoginData>, // data 是可选的，因为可能会有错误
-}
+use cli::{Cli, Command, GlobalOpts};
 
-#[derive(Serialize, Deserialize, Debug)]
-struct LoginData {
-    token: String,
+/// What a failed command prints to stdout with `--json`.
+#[derive(Serialize)]
+struct ErrorResult {
+    status: &'static str,
+    /// The process exit status.
+    code: i32,
+    message: String,
 }
 
 #[tokio::main]
-async fn main() {
-    let args: Vec<String> = env::args().collect();
-
-    if args.len() != 7 {
-        eprintln!(
-            "Usage: {} --username <username> --password <password> <local-file> <alist-url>",
-            args[0]
-        );
-        process::exit(1);
+async fn main() -> process::ExitCode {
+    let mut cli = Cli::parse();
+    init_logging(&cli.global);
+    let started = SystemTime::now();
+    if cli.global.log_file.is_some() {
+        commands::audit::enable();
     }
 
-    let mut args_iter = args.iter();
-    args_iter.next();
-
-    let username = match args_iter.find(|x| *x == "--username") {
-        Some(_) => args_iter.next().unwrap(),
-        None => {
-            eprintln!("Error: --username flag is required");
-            process::exit(1);
-        }
-    };
-
-    let password = match args_iter.find(|&x| x == "--password") {
-        Some(_) => args_iter.next().unwrap(),
-        None => {
-            eprintln!("Error: --password flag is required");
-            process::exit(1);
-        }
+    // Dropping the command's future on Ctrl-C closes any connection it holds,
+    // so an upload in progress is cut off instead of lingering.
+    let result = tokio::select! {
+        result = run(&mut cli) => result,
+        _ = tokio::signal::ctrl_c() => Err(AlistError::Aborted),
     };
-
-    let local_file = match args_iter.next() {
-        Some(arg) => arg,
-        None => {
-            eprintln!("Error: Local file path is required");
-            process::exit(1);
+    if cli.global.time {
+        commands::print_timings();
+    }
+    if let Some(path) = &cli.global.log_file {
+        let exit_status = ExitCode::of(&result).code();
+        if let Err(err) = commands::audit::write(path, started, exit_status, result.as_ref().err()) {
+            warn!("Could not write to the log file {}: {}", path.display(), err);
         }
-    };
+    }
 
-    let remote_path = match args_iter.next() {
-        Some(arg) => arg,
-        None => {
-            eprintln!("Error: Remote path is required");
-            process::exit(1);
+    let code = ExitCode::of(&result);
+    if let Err(err) = result {
+        if matches!(err, AlistError::Aborted) {
+            commands::remove_temporary().await;
+            let as_task = match &cli.command {
+                Command::Upload(args) => args.options.as_task,
+                Command::Batch(args) => args.options.as_task,
+                _ => false,
+            };
+            if as_task {
+                eprintln!("Note: background tasks already started on the server may keep running");
+            }
         }
-    };
-
-    let base_url = match Url::parse(remote_path) {
-        Ok(parsed_url) => parsed_url[..url::Position::BeforePath].to_string(),
-        Err(_) => {
-            eprintln!("Error: Invalid URL");
-            process::exit(1);
+        if cli.global.json {
+            let error = ErrorResult {
+                status: "error",
+                code: code.code(),
+                message: err.to_string(),
+            };
+            println!("{}", serde_json::to_string(&error).expect("error result serializes"));
+        } else if !(cli.global.quiet && matches!(err, AlistError::NotFound(_))) {
+            eprintln!("Error: {}", err);
+            if cli.global.anonymous && matches!(err, AlistError::Auth { .. }) {
+                eprintln!("The path is not public; log in with --username or --token instead of --anonymous");
+            }
         }
-    };
-
-    // Get Token: https://alist.nn.ci/guide/api/fs.html#put-流式上传文件
-    let client = Client::new();
-    let login_url = format!("{}/api/auth/login", base_url);
-    let login_response = client
-        .post(&login_url)
-        .json(&LoginRequest {
-            username: username.clone(),
-            password: password.clone(),
-        })
-        .send()
-        .await
-        .expect("Failed to send login request");
-
-    let text_response = login_response
-        .text()
-        .await
-        .expect("Failed to parse login response");
-
-    let parsed_response: LoginResponse =
-        serde_json::from_str(&text_response).expect("Failed to deserialize response");
+    }
+    code.into()
+}
 
-    let token;
+async fn run(cli: &mut Cli) -> Result<()> {
+    if cli.global.quiet {
+        cli.global.no_progress = true;
+    }
+    let profiles = cli.global.selected_profiles()?;
+    if !profiles.is_empty() {
+        return commands::profiles::cmd_each_profile(&cli.global, &profiles, &cli.command).await;
+    }
+    cli.global.resolve()?;
+    let public = matches!(
+        cli.command,
+        Command::List(_) | Command::Download(_) | Command::Cat { .. } | Command::Link(_)
+    );
+    if cli.global.anonymous && !public {
+        return Err(AlistError::Config(
+            "--anonymous only works with `ls`, `download`, `cat` and `link`".to_string(),
+        ));
+    }
 
-    if parsed_response.message == "success" {
-        if let Some(data) = parsed_response.data {
-            token = data.token;
-        } else {
-            eprintln!("Error: No token received in response data");
-            std::process::exit(1);
+    match &cli.command {
+        Command::Upload(args) => commands::upload::cmd_upload(&cli.global, args).await,
+        Command::Sync(args) => commands::sync::cmd_sync(&cli.global, args).await,
+        Command::Batch(args) => commands::batch::cmd_batch(&cli.global, args).await,
+        Command::Download(args) => commands::download::cmd_download(&cli.global, args).await,
+        Command::Cat { alist_url, tee, sign } => {
+            commands::download::cmd_cat(&cli.global, alist_url, tee.as_deref(), sign.as_deref(), None).await
+        }
+        Command::Link(args) => commands::download::cmd_link(&cli.global, args).await,
+        Command::List(args) => commands::list::cmd_list(&cli.global, args).await,
+        Command::Search(args) => commands::list::cmd_search(&cli.global, args).await,
+        Command::Mkdir(args) => commands::fs::cmd_mkdir(&cli.global, args).await,
+        Command::Rm(args) => commands::fs::cmd_rm(&cli.global, args).await,
+        Command::Mv(args) => commands::fs::cmd_mv(&cli.global, args).await,
+        Command::Cp(args) => commands::fs::cmd_cp(&cli.global, args).await,
+        Command::Rename(args) => commands::fs::cmd_rename(&cli.global, args).await,
+        Command::Exists { alist_url } => commands::fs::cmd_exists(&cli.global, alist_url).await,
+        Command::Verify(args) => commands::verify::cmd_verify(&cli.global, args).await,
+        Command::OfflineDownload(args) => commands::offline::cmd_offline_download(&cli.global, args).await,
+        Command::Tasks { command } => commands::tasks::cmd_tasks(&cli.global, command).await,
+        Command::Storage { command } => commands::storage::cmd_storage(&cli.global, command).await,
+        Command::Info { alist_url } => commands::auth::cmd_info(&cli.global, alist_url.as_deref()).await,
+        Command::Doctor { alist_url } => {
+            commands::doctor::cmd_doctor(&cli.global, alist_url.as_deref()).await
+        }
+        Command::Me { alist_url } => commands::auth::cmd_me(&cli.global, alist_url.as_deref()).await,
+        Command::Api(args) => commands::api::cmd_api(&cli.global, args).await,
+        Command::Login { alist_url, save } => {
+            commands::auth::cmd_login(&cli.global, alist_url.as_deref(), *save).await
+        }
+        Command::Logout { alist_url } => commands::auth::cmd_logout(&cli.global, alist_url.as_deref()).await,
+        Command::Completions { shell } => {
+            let mut command = Cli::command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(*shell, &mut command, name, &mut std::io::stdout());
+            Ok(())
         }
-    } else {
-        eprintln!("Login failed with message: {}", parsed_response.message);
-        std::process::exit(1);
     }
+}
 
-    // Upload File: https://alist.nn.ci/guide/api/fs.html#put-流式上传文件
-    let remote_file_path = remote_path.replace(&base_url, "");
-    let upload_url = format!("{}/api/fs/put", base_url);
-    let mut headers = HeaderMap::new();
-    headers.insert(AUTHORIZATION, format!("{}", token).parse().unwrap());
-    headers.insert("File-Path", remote_file_path.parse().unwrap());
-
-    let file = File::open(local_file).await.expect("Failed to open file");
-    // 将文件转换为异步字节流
-    let file_stream = FramedRead::new(file, BytesCodec::new())
-        .map(|result| result.map(|bytes| bytes.freeze()));
-
-    let body = reqwest::Body::wrap_stream(file_stream);
-
-    let upload_response = client
-        .put(&upload_url)
-        .headers(headers)
-        .body(body)
-        .send()
-        .await
-        .expect("Failed to send upload request");
-
-    let text_response = upload_response
-        .text()
-        .await
-        .expect("Failed to parse login response");
-
-    println!("Upload response: {:?}", text_response);
+/// Send diagnostics to stderr. `RUST_LOG` is honoured unless -v or -q is given;
+/// `--trace-body` adds the bodies on top of either.
+fn init_logging(opts: &GlobalOpts) {
+    let level = match (opts.quiet, opts.verbose) {
+        (true, _) => Some("error"),
+        (false, 0) => None,
+        (false, 1) => Some("debug"),
+        (false, _) => Some("trace"),
+    };
+    let mut filter = match level {
+        Some(level) => EnvFilter::new(level),
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn")),
+    };
+    if opts.trace_body {
+        let directive = format!("{}=trace", alist_cli::client::BODY_TARGET);
+        filter = filter.add_directive(directive.parse().expect("valid filter directive"));
+    }
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .without_time()
+        .with_target(false)
+        .init();
 }
+
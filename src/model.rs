@@ -0,0 +1,391 @@
+//! Request and response bodies of the Alist API.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::hash::HashInfo;
+
+/// The envelope every Alist API response is wrapped in.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiResponse<T> {
+    pub code: u16,
+    pub message: String,
+    pub data: Option<T>, // data 是可选的，因为可能会有错误
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LoginRequest {
+    pub username: String,
+    pub password: String,
+    /// Two-factor code, for accounts that have 2FA enabled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub otp_code: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LoginData {
+    pub token: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PathRequest {
+    pub path: String,
+}
+
+/// Request body of `fs/get`. `password` unlocks password-protected folders.
+#[derive(Serialize, Deserialize)]
+pub struct GetRequest {
+    pub path: String,
+    pub password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetData {
+    pub name: String,
+    #[serde(default)]
+    pub size: u64,
+    pub is_dir: bool,
+    /// Empty for directories.
+    #[serde(default)]
+    pub raw_url: String,
+    /// Signature for the `/d/` download link, when the storage requires signed links.
+    #[serde(default)]
+    pub sign: String,
+    /// Hashes the storage driver reports, keyed by algorithm (`md5`, `sha256`, ...).
+    #[serde(default)]
+    pub hash_info: Option<HashInfo>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ListRequest {
+    pub path: String,
+    pub password: String,
+    pub page: u32,
+    /// `0` asks the server for every entry at once.
+    pub per_page: u32,
+    pub refresh: bool,
+}
+
+impl ListRequest {
+    pub fn new(path: impl Into<String>) -> Self {
+        ListRequest {
+            path: path.into(),
+            password: String::new(),
+            page: 1,
+            per_page: 0,
+            refresh: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ListData {
+    pub content: Option<Vec<FileEntry>>,
+    #[serde(default)]
+    pub total: u64,
+}
+
+/// One entry of a directory listing.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileEntry {
+    pub name: String,
+    #[serde(default)]
+    pub size: u64,
+    pub is_dir: bool,
+    #[serde(default)]
+    pub modified: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash_info: Option<HashInfo>,
+}
+
+/// What kind of entries a search returns.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SearchScope {
+    #[default]
+    All = 0,
+    Folders = 1,
+    Files = 2,
+}
+
+impl std::str::FromStr for SearchScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(SearchScope::All),
+            "folder" | "folders" => Ok(SearchScope::Folders),
+            "file" | "files" => Ok(SearchScope::Files),
+            _ => Err(format!("unknown scope `{}`, expected `all`, `folder` or `file`", s)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchRequest {
+    pub parent: String,
+    pub keywords: String,
+    /// A [`SearchScope`] as a number.
+    pub scope: u8,
+    pub page: u32,
+    pub per_page: u32,
+    pub password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SearchData {
+    #[serde(default)]
+    pub content: Option<Vec<SearchEntry>>,
+    #[serde(default)]
+    pub total: u64,
+}
+
+/// One search hit.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchEntry {
+    pub parent: String,
+    pub name: String,
+    #[serde(default)]
+    pub is_dir: bool,
+    #[serde(default)]
+    pub size: u64,
+}
+
+/// The downloader an offline download runs through on the server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OfflineTool {
+    #[default]
+    Aria2,
+    QBittorrent,
+    SimpleHttp,
+}
+
+impl OfflineTool {
+    /// The name the server knows the tool by.
+    pub fn name(self) -> &'static str {
+        match self {
+            OfflineTool::Aria2 => "aria2",
+            OfflineTool::QBittorrent => "qBittorrent",
+            OfflineTool::SimpleHttp => "SimpleHttp",
+        }
+    }
+}
+
+impl std::str::FromStr for OfflineTool {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "aria2" => Ok(OfflineTool::Aria2),
+            "qbittorrent" | "qbit" => Ok(OfflineTool::QBittorrent),
+            "simplehttp" | "http" => Ok(OfflineTool::SimpleHttp),
+            _ => Err(format!(
+                "unknown tool `{}`, expected `aria2`, `qbittorrent` or `simplehttp`",
+                s
+            )),
+        }
+    }
+}
+
+/// Body of `fs/add_offline_download`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OfflineDownloadRequest {
+    pub path: String,
+    pub urls: Vec<String>,
+    /// An [`OfflineTool`] name.
+    pub tool: String,
+    pub delete_policy: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RenameRequest {
+    pub path: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RemoveRequest {
+    pub dir: String,
+    pub names: Vec<String>,
+}
+
+/// Body of `fs/move` and `fs/copy`.
+#[derive(Serialize, Deserialize)]
+pub struct MoveRequest {
+    pub src_dir: String,
+    pub dst_dir: String,
+    pub names: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct PutData {
+    /// Set when the upload was submitted with `As-Task: true`.
+    #[serde(default)]
+    pub task: Option<TaskInfo>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct CopyData {
+    /// Tasks created for the copy. Older servers return no data at all.
+    #[serde(default)]
+    pub tasks: Vec<TaskInfo>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct OfflineDownloadData {
+    #[serde(default)]
+    pub tasks: Vec<TaskInfo>,
+}
+
+/// The settings Alist shares without logging in, from `/api/public/settings`.
+/// Values are strings, as the server stores them.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PublicSettings {
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub site_title: String,
+    #[serde(default)]
+    pub announcement: String,
+    /// Whether search sees folders not under a mount point.
+    #[serde(default)]
+    pub allow_indexed: String,
+    #[serde(default)]
+    pub allow_mounted: String,
+    /// Everything else the server sent, which varies between versions.
+    #[serde(flatten)]
+    pub other: BTreeMap<String, serde_json::Value>,
+}
+
+/// The account behind a token, from `/api/me`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UserInfo {
+    pub id: u64,
+    pub username: String,
+    /// The directory the user's view of the storage is rooted at.
+    #[serde(default)]
+    pub base_path: String,
+    pub role: u8,
+    #[serde(default)]
+    pub disabled: bool,
+    /// Bit set of the user's permissions; see [`permission_names`](Self::permission_names).
+    #[serde(default)]
+    pub permission: u32,
+}
+
+impl UserInfo {
+    pub const GENERAL: u8 = 0;
+    pub const GUEST: u8 = 1;
+    pub const ADMIN: u8 = 2;
+
+    /// What each bit of `permission` allows, lowest bit first.
+    const PERMISSIONS: [&'static str; 14] = [
+        "see hidden files",
+        "access without password",
+        "add offline download",
+        "write",
+        "rename",
+        "move",
+        "copy",
+        "remove",
+        "webdav read",
+        "webdav manage",
+        "ftp read",
+        "ftp manage",
+        "read archives",
+        "decompress",
+    ];
+
+    pub fn role_name(&self) -> &'static str {
+        match self.role {
+            Self::GENERAL => "general",
+            Self::GUEST => "guest",
+            Self::ADMIN => "admin",
+            _ => "unknown",
+        }
+    }
+
+    /// The names of the permissions whose bits are set.
+    pub fn permission_names(&self) -> Vec<&'static str> {
+        Self::PERMISSIONS
+            .iter()
+            .enumerate()
+            .filter(|(bit, _)| self.permission & (1 << bit) != 0)
+            .map(|(_, name)| *name)
+            .collect()
+    }
+}
+
+/// A storage mounted on the server, as admins configure them.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Storage {
+    pub id: u64,
+    pub mount_path: String,
+    pub driver: String,
+    #[serde(default)]
+    pub order: i64,
+    /// `work` when the storage is up, otherwise the error it failed with.
+    #[serde(default)]
+    pub status: String,
+    #[serde(default)]
+    pub remark: String,
+    #[serde(default)]
+    pub disabled: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StorageList {
+    pub content: Option<Vec<Storage>>,
+    #[serde(default)]
+    pub total: u64,
+}
+
+/// A background task as reported by Alist's task manager.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TaskInfo {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub state: u8,
+    #[serde(default)]
+    pub status: String,
+    #[serde(default)]
+    pub progress: f64,
+    #[serde(default)]
+    pub error: String,
+}
+
+impl TaskInfo {
+    /// The task managers a server may run, as named in `/api/admin/task/{kind}/...`.
+    pub const KINDS: &'static [&'static str] =
+        &["upload", "copy", "offline_download", "offline_download_transfer"];
+
+    pub const SUCCEEDED: u8 = 2;
+    pub const CANCELED: u8 = 4;
+    pub const ERRORED: u8 = 5;
+    pub const FAILED: u8 = 7;
+
+    /// Whether the task has stopped and will not make further progress.
+    pub fn is_finished(&self) -> bool {
+        matches!(self.state, Self::SUCCEEDED | Self::CANCELED | Self::FAILED)
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.state == Self::SUCCEEDED
+    }
+
+    pub fn state_name(&self) -> &'static str {
+        match self.state {
+            0 => "pending",
+            1 => "running",
+            Self::SUCCEEDED => "succeeded",
+            3 => "canceling",
+            Self::CANCELED => "canceled",
+            Self::ERRORED => "errored",
+            6 => "failing",
+            Self::FAILED => "failed",
+            8 => "waiting retry",
+            9 => "before retry",
+            _ => "unknown",
+        }
+    }
+}
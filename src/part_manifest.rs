@@ -0,0 +1,81 @@
+//! How much of a large upload the server has confirmed, kept in a small `.part` file
+//! next to the local file, so an upload cut off part way can carry on from there.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::checkpoint::stat;
+use crate::error::Result;
+
+/// The contents of a manifest.
+#[derive(Serialize, Deserialize, Debug)]
+struct Uploaded {
+    remote: String,
+    size: u64,
+    /// Modification time in seconds and nanoseconds since the Unix epoch.
+    mtime: (u64, u32),
+    /// How many bytes from the start of the file the server has stored.
+    offset: u64,
+}
+
+/// The manifest of one local file being uploaded to one remote path.
+#[derive(Debug)]
+pub struct PartManifest {
+    path: PathBuf,
+    uploaded: Uploaded,
+}
+
+impl PartManifest {
+    /// Where the manifest for `local` is kept: a hidden file next to it ending in `.part`.
+    pub fn path_for(local: &Path) -> PathBuf {
+        let name = local.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        local.with_file_name(format!(".{}.alist-cli.part", name))
+    }
+
+    /// Read the manifest for uploading `local` to `remote`. One left by an upload to
+    /// elsewhere, or from before the file changed, or that cannot be read, starts
+    /// again from the beginning.
+    pub fn open(local: &Path, remote: &str) -> Result<Self> {
+        let path = Self::path_for(local);
+        let (size, mtime) = stat(local)?;
+        let mut uploaded = Uploaded {
+            remote: remote.to_string(),
+            size,
+            mtime,
+            offset: 0,
+        };
+        let earlier = match fs::read(&path) {
+            Ok(contents) => serde_json::from_slice::<Uploaded>(&contents).ok(),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err.into()),
+        };
+        if let Some(earlier) = earlier {
+            if (&earlier.remote, earlier.size, earlier.mtime) == (&uploaded.remote, size, mtime) {
+                uploaded.offset = earlier.offset.min(size);
+            }
+        }
+        Ok(PartManifest { path, uploaded })
+    }
+
+    /// How many bytes from the start of the file are already on the server.
+    pub fn offset(&self) -> u64 {
+        self.uploaded.offset
+    }
+
+    /// Note that the server has stored the file up to `offset`, writing it out at once.
+    pub fn confirm(&mut self, offset: u64) -> Result<()> {
+        self.uploaded.offset = offset;
+        fs::write(&self.path, serde_json::to_vec(&self.uploaded)?)?;
+        Ok(())
+    }
+
+    /// Delete the manifest, once the upload is complete or has to start over.
+    pub fn remove(self) -> Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! Helpers for remote Alist paths.
+
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use std::path::{Component, Path};
+
+/// Characters left as-is by JavaScript's `encodeURIComponent`, which is what the
+/// Alist web UI uses for the `File-Path` header.
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'!')
+    .remove(b'~')
+    .remove(b'*')
+    .remove(b'\'')
+    .remove(b'(')
+    .remove(b')');
+
+/// Percent-encode every segment of `path`, keeping the `/` separators intact.
+pub fn encode_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| utf8_percent_encode(segment, SEGMENT).to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Join a remote directory and an entry name.
+pub fn join(dir: &str, name: &str) -> String {
+    format!("{}/{}", dir.trim_end_matches('/'), name)
+}
+
+/// Append a relative local path to the remote directory `base`, using `/` separators.
+pub fn join_relative(base: &str, relative: &Path) -> String {
+    let mut joined = base.trim_end_matches('/').to_string();
+    for component in relative.components() {
+        if let Component::Normal(name) = component {
+            joined.push('/');
+            joined.push_str(&name.to_string_lossy());
+        }
+    }
+    if joined.is_empty() {
+        joined.push('/');
+    }
+    joined
+}
+
+/// Resolve `path` below the remote directory `base`, e.g. `movies/a.mkv` below
+/// `/media` to `/media/movies/a.mkv`. The result is always absolute.
+pub fn under(base: &str, path: &str) -> String {
+    let segments: Vec<&str> = base
+        .split('/')
+        .chain(path.split('/'))
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    format!("/{}", segments.join("/"))
+}
+
+/// Resolve `path` against the remote working directory `cwd`, as a shell would: a
+/// path starting with `/` is taken as it is, and `.` and `..` segments are followed,
+/// with `..` going no higher than `/`. The result is always absolute.
+pub fn resolve(cwd: &str, path: &str) -> String {
+    let start = if path.starts_with('/') { "" } else { cwd };
+    let mut segments = Vec::new();
+    for segment in start.split('/').chain(path.split('/')) {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    format!("/{}", segments.join("/"))
+}
+
+/// The part of `path` below the remote directory `prefix`, without a leading `/`,
+/// e.g. `movies/a.mkv` for `/media/movies/a.mkv` below `/media`. `None` if `path` is
+/// not below `prefix`; `/media2` is not below `/media`.
+pub fn strip_prefix<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    let prefix = prefix.trim_end_matches('/');
+    let rest = path.strip_prefix(prefix)?;
+    if rest.is_empty() || rest.starts_with('/') {
+        Some(rest.trim_start_matches('/'))
+    } else {
+        None
+    }
+}
+
+/// Split a remote path into its parent directory and final name, e.g.
+/// `/dir/file.txt` into `/dir` and `file.txt`.
+pub fn split_parent(path: &str) -> (&str, &str) {
+    let path = path.trim_end_matches('/');
+    match path.rfind('/') {
+        Some(0) => ("/", &path[1..]),
+        Some(index) => (&path[..index], &path[index + 1..]),
+        None => ("/", path),
+    }
+}
+
+/// Where a file named `name` goes when uploaded to `dest`, as `cp` would place it:
+/// inside `dest` if it ends in `/` or `dest_is_dir` says it is a directory, and at
+/// `dest` itself otherwise.
+pub fn dest_path(dest: &str, name: &str, dest_is_dir: bool) -> String {
+    if dest_is_dir || dest.ends_with('/') {
+        join(dest, name)
+    } else {
+        dest.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_unicode_segments() {
+        assert_eq!(encode_path("/dir/文件.txt"), "/dir/%E6%96%87%E4%BB%B6.txt");
+    }
+
+    #[test]
+    fn encodes_spaces_but_keeps_separators() {
+        assert_eq!(encode_path("/my folder/a b.txt"), "/my%20folder/a%20b.txt");
+    }
+
+    #[test]
+    fn leaves_plain_paths_untouched() {
+        assert_eq!(encode_path("/backups/2024-01-01_db.tar.gz"), "/backups/2024-01-01_db.tar.gz");
+    }
+
+    #[test]
+    fn resolves_paths_under_a_base() {
+        assert_eq!(under("/media", "movies/a.mkv"), "/media/movies/a.mkv");
+        assert_eq!(under("/media/", "/movies/"), "/media/movies");
+        assert_eq!(under("/", "a.mkv"), "/a.mkv");
+        assert_eq!(under("/", "/"), "/");
+    }
+
+    #[test]
+    fn resolves_relative_paths_against_the_working_directory() {
+        assert_eq!(resolve("/media/movies", "a.mkv"), "/media/movies/a.mkv");
+        assert_eq!(resolve("/media/movies", "/music/b.mp3"), "/music/b.mp3");
+        assert_eq!(resolve("/media/movies/", "../music/./b.mp3"), "/media/music/b.mp3");
+        assert_eq!(resolve("/media", "../../.."), "/");
+        assert_eq!(resolve("/", "a.mkv"), "/a.mkv");
+    }
+
+    #[test]
+    fn strips_whole_segment_prefixes() {
+        assert_eq!(strip_prefix("/media/movies/a.mkv", "/media"), Some("movies/a.mkv"));
+        assert_eq!(strip_prefix("/media/movies/a.mkv", "/media/"), Some("movies/a.mkv"));
+        assert_eq!(strip_prefix("/media/a.mkv", "/"), Some("media/a.mkv"));
+        assert_eq!(strip_prefix("/media", "/media"), Some(""));
+        assert_eq!(strip_prefix("/media2/a.mkv", "/media"), None);
+        assert_eq!(strip_prefix("/other/a.mkv", "/media"), None);
+    }
+
+    #[test]
+    fn splits_parent_and_name() {
+        assert_eq!(split_parent("/dir/sub/file.txt"), ("/dir/sub", "file.txt"));
+        assert_eq!(split_parent("/file.txt"), ("/", "file.txt"));
+        assert_eq!(split_parent("/dir/"), ("/", "dir"));
+    }
+
+    #[test]
+    fn uploads_into_directories_under_the_local_name() {
+        assert_eq!(dest_path("/docs/", "a.txt", false), "/docs/a.txt");
+        assert_eq!(dest_path("/", "a.txt", false), "/a.txt");
+        assert_eq!(dest_path("/docs", "a.txt", true), "/docs/a.txt");
+        assert_eq!(dest_path("/docs/b.txt", "a.txt", false), "/docs/b.txt");
+    }
+}
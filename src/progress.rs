@@ -0,0 +1,249 @@
+//! Progress reporting for transfers.
+
+use futures_util::stream::{Stream, StreamExt};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressState, ProgressStyle, TermLike};
+use serde::Serialize;
+use std::io::{self, Write as _};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+const BAR_TEMPLATE: &str =
+    "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})";
+const SPINNER_TEMPLATE: &str = "{spinner:.green} [{elapsed_precise}] {bytes} ({bytes_per_sec})";
+const TOTAL_TEMPLATE: &str = concat!(
+    "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ",
+    "({bytes_per_sec}, {eta}) {msg}"
+);
+const TASK_TEMPLATE: &str = "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos:>3}% {prefix} {msg}";
+
+/// How many `--progress-format json` events a transfer emits per second at most.
+const EVENTS_PER_SEC: u8 = 2;
+
+/// How progress is shown on stderr.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProgressFormat {
+    /// A bar redrawn in place, for people.
+    #[default]
+    Bar,
+    /// One [`ProgressEvent`] per line, for programs.
+    Json,
+}
+
+impl std::str::FromStr for ProgressFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "bar" => Ok(ProgressFormat::Bar),
+            "json" => Ok(ProgressFormat::Json),
+            _ => Err(format!("unknown progress format `{}`, expected `bar` or `json`", s)),
+        }
+    }
+}
+
+/// The state of one transfer, as printed by [`json_bar`].
+#[derive(Debug, Serialize)]
+pub struct ProgressEvent<'a> {
+    pub name: &'a str,
+    /// Bytes transferred so far.
+    pub bytes: u64,
+    /// Size of the transfer, when known.
+    pub total: Option<u64>,
+    pub percent: Option<f64>,
+    pub bytes_per_sec: f64,
+    /// Set on the last event of a transfer.
+    pub done: bool,
+}
+
+/// Create a bar for a transfer of `total` bytes, or a spinner when the size is unknown.
+pub fn transfer_bar(total: Option<u64>) -> ProgressBar {
+    match total {
+        Some(len) => ProgressBar::new(len).with_style(
+            ProgressStyle::with_template(BAR_TEMPLATE)
+                .expect("valid progress template")
+                .progress_chars("#>-"),
+        ),
+        None => ProgressBar::new_spinner()
+            .with_style(ProgressStyle::with_template(SPINNER_TEMPLATE).expect("valid progress template")),
+    }
+}
+
+/// One bar for transfers running side by side: their bytes together, and how many of
+/// the files are done. The bars of the transfers themselves are only drawn, above it,
+/// with `per_file`.
+pub struct TotalProgress {
+    bars: MultiProgress,
+    bar: ProgressBar,
+    per_file: bool,
+    files: usize,
+    done: AtomicUsize,
+    transfers: Mutex<Vec<ProgressBar>>,
+}
+
+impl TotalProgress {
+    /// Add the bar for `files` transfers to `bars`.
+    pub fn new(bars: &MultiProgress, files: usize, per_file: bool) -> Self {
+        let bar = bars.add(ProgressBar::new(0).with_style(
+            ProgressStyle::with_template(TOTAL_TEMPLATE)
+                .expect("valid progress template")
+                .progress_chars("#>-"),
+        ));
+        bar.set_message(format!("0/{} files", files));
+        TotalProgress {
+            bars: bars.clone(),
+            bar,
+            per_file,
+            files,
+            done: AtomicUsize::new(0),
+            transfers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Create a bar for a transfer of `size` bytes, counted towards the total.
+    pub fn transfer(&self, size: Option<u64>) -> ProgressBar {
+        let bar = transfer_bar(size);
+        let bar = if self.per_file {
+            self.bars.insert_before(&self.bar, bar)
+        } else {
+            bar.set_draw_target(ProgressDrawTarget::hidden());
+            bar
+        };
+        self.bar.inc_length(size.unwrap_or(0));
+        self.transfers.lock().expect("transfers lock").push(bar.clone());
+        bar
+    }
+
+    /// Count one more file as done, whether or not it succeeded.
+    pub fn file_done(&self) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        self.bar.set_message(format!("{}/{} files", done, self.files));
+    }
+
+    /// Bring the total up to date with the transfers every `interval`, for as long as
+    /// the future is polled; it never finishes.
+    pub async fn refresh(&self, interval: Duration) {
+        loop {
+            tokio::time::sleep(interval).await;
+            self.update();
+        }
+    }
+
+    fn update(&self) {
+        let transfers = self.transfers.lock().expect("transfers lock");
+        self.bar.set_position(transfers.iter().map(ProgressBar::position).sum());
+    }
+
+    pub fn finish(&self) {
+        self.update();
+        self.bar.finish();
+    }
+
+    /// Run `f`, such as printing a line, with the bars cleared from the terminal.
+    pub fn suspend<R>(&self, f: impl FnOnce() -> R) -> R {
+        self.bars.suspend(f)
+    }
+}
+
+/// Create a bar for a background task named `name`, measured in percent.
+pub fn task_bar(name: &str) -> ProgressBar {
+    ProgressBar::new(100)
+        .with_style(
+            ProgressStyle::with_template(TASK_TEMPLATE)
+                .expect("valid progress template")
+                .progress_chars("#>-"),
+        )
+        .with_prefix(name.to_string())
+}
+
+/// Create a bar that, instead of drawing itself, writes a [`ProgressEvent`] for the
+/// transfer `name` as a JSON line to stderr, a few times a second and once it ends.
+pub fn json_bar(name: &str, total: Option<u64>) -> ProgressBar {
+    let name = name.to_string();
+    let style = ProgressStyle::with_template("{event}")
+        .expect("valid progress template")
+        .with_key("event", move |state: &ProgressState, out: &mut dyn std::fmt::Write| {
+            let event = ProgressEvent {
+                name: &name,
+                bytes: state.pos(),
+                total: state.len(),
+                percent: state
+                    .len()
+                    .map(|len| if len == 0 { 100.0 } else { state.pos() as f64 * 100.0 / len as f64 }),
+                bytes_per_sec: state.per_sec(),
+                done: state.is_finished(),
+            };
+            let line = serde_json::to_string(&event).expect("progress event serializes");
+            let _ = out.write_str(&line);
+        });
+    let target = ProgressDrawTarget::term_like_with_hz(Box::new(JsonLines::default()), EVENTS_PER_SEC);
+    ProgressBar::with_draw_target(total, target).with_style(style)
+}
+
+/// A pretend terminal that prints each frame drawn on it as a line of its own, so
+/// frames are appended to stderr instead of overwriting each other.
+#[derive(Debug, Default)]
+struct JsonLines {
+    frame: Mutex<String>,
+}
+
+impl TermLike for JsonLines {
+    fn width(&self) -> u16 {
+        u16::MAX
+    }
+
+    fn move_cursor_up(&self, _n: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn move_cursor_down(&self, _n: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn move_cursor_right(&self, _n: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn move_cursor_left(&self, _n: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_line(&self, s: &str) -> io::Result<()> {
+        let mut frame = self.frame.lock().expect("progress frame lock");
+        frame.push_str(s);
+        frame.push('\n');
+        Ok(())
+    }
+
+    fn write_str(&self, s: &str) -> io::Result<()> {
+        self.frame.lock().expect("progress frame lock").push_str(s);
+        Ok(())
+    }
+
+    fn clear_line(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        let frame = std::mem::take(&mut *self.frame.lock().expect("progress frame lock"));
+        let mut stderr = io::stderr().lock();
+        for line in frame.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            writeln!(stderr, "{}", line)?;
+        }
+        stderr.flush()
+    }
+}
+
+/// Advance `bar` by the length of every chunk `stream` yields.
+pub fn track<S, B, E>(stream: S, bar: ProgressBar) -> impl Stream<Item = Result<B, E>>
+where
+    S: Stream<Item = Result<B, E>>,
+    B: AsRef<[u8]>,
+{
+    stream.map(move |chunk| {
+        if let Ok(bytes) = &chunk {
+            bar.inc(bytes.as_ref().len() as u64);
+        }
+        chunk
+    })
+}
@@ -0,0 +1,253 @@
+//! Retrying requests that failed for transient reasons.
+
+use chrono::DateTime;
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Response, StatusCode};
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+use crate::error::{AlistError, Result};
+
+/// How often and how patiently to retry a failed request.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; `0` disables retrying.
+    pub retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// The longest wait a server's `Retry-After` header is followed for.
+    pub max_retry_after: Duration,
+    /// Alist `code`s in otherwise successful responses that mean a transient failure,
+    /// such as a storage driver error, and are retried like one.
+    pub retry_codes: Vec<u16>,
+    /// Retry requests that are not idempotent, such as moves, like any other, instead
+    /// of only when they cannot have reached the server.
+    pub force: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            retries: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            max_retry_after: Duration::from_secs(60),
+            retry_codes: Vec::new(),
+            force: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn with_retries(retries: u32) -> Self {
+        RetryPolicy {
+            retries,
+            ..Default::default()
+        }
+    }
+
+    /// Exponential backoff with jitter: a random delay between half and all of
+    /// `base_delay * 2^attempt`, capped at `max_delay`.
+    fn delay(&self, attempt: u32) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        let jitter = RandomState::new().build_hasher().finish() % 1000;
+        backoff / 2 + backoff / 2 * jitter as u32 / 1000
+    }
+
+    /// Whether `err` is worth retrying: if [`is_transient`], or an API error with one of
+    /// the `retry_codes`.
+    pub fn should_retry(&self, err: &AlistError) -> bool {
+        is_transient(err) || matches!(err, AlistError::Api { code, .. } if self.retry_codes.contains(code))
+    }
+
+    /// Whether a request that is not idempotent is worth retrying after `err`: only if
+    /// the server never acted on it, because the connection could not be made or the
+    /// server turned it away to slow down, unless `force` is set. A timeout or a `5xx`
+    /// leaves open whether the server carried it out before failing.
+    pub fn should_retry_mutation(&self, err: &AlistError) -> bool {
+        if self.force {
+            return self.should_retry(err);
+        }
+        match err.root() {
+            AlistError::Throttled { .. } => true,
+            AlistError::Http(err) => err.is_connect() && !err.is_timeout(),
+            _ => false,
+        }
+    }
+
+    /// How long to wait before retrying after `err`: what the server asked for, up
+    /// to `max_retry_after`, or else the backoff.
+    fn delay_after(&self, err: &AlistError, attempt: u32) -> Duration {
+        match err.root() {
+            AlistError::Throttled {
+                retry_after: Some(retry_after),
+                ..
+            } => (*retry_after).min(self.max_retry_after),
+            _ => self.delay(attempt),
+        }
+    }
+}
+
+/// Turn an HTTP error status into an error, like `Response::error_for_status`, but
+/// keep the `Retry-After` hint of `429 Too Many Requests` and `503 Service
+/// Unavailable` answers as [`AlistError::Throttled`].
+pub fn check_status(response: Response) -> Result<Response> {
+    let status = response.status();
+    if status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE {
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| parse_retry_after(value, SystemTime::now()));
+        return Err(AlistError::Throttled {
+            status: status.as_u16(),
+            retry_after,
+        });
+    }
+    Ok(response.error_for_status()?)
+}
+
+/// Read a `Retry-After` value, either a number of seconds or an HTTP date, as the
+/// time left to wait from `now`.
+pub fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    let now = now.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+    Some(Duration::from_secs(date.timestamp().saturating_sub(now).max(0) as u64))
+}
+
+/// Whether `err` is worth retrying: connection failures, timeouts, and
+/// `429 Too Many Requests` or `5xx` answers.
+pub fn is_transient(err: &AlistError) -> bool {
+    match err.root() {
+        AlistError::Throttled { .. } => true,
+        AlistError::Http(err) => {
+            err.is_connect()
+                || err.is_timeout()
+                || err
+                    .status()
+                    .is_some_and(|status| status.as_u16() == 429 || status.is_server_error())
+        }
+        _ => false,
+    }
+}
+
+/// Run `op` until it succeeds, fails with a non-transient error, or the policy's
+/// retries are used up, in which case the last error is returned.
+///
+/// `op` is called afresh for each attempt, so anything consumed by a request, such
+/// as a streaming body, must be re-created inside it.
+pub async fn run<T, F, Fut>(policy: &RetryPolicy, op: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    retry_while(policy, op, |err| policy.should_retry(err)).await
+}
+
+/// Like [`run`], for a request that must not be carried out twice, such as a move or
+/// a removal: it is only retried as [`RetryPolicy::should_retry_mutation`] allows.
+pub async fn run_mutation<T, F, Fut>(policy: &RetryPolicy, op: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    retry_while(policy, op, |err| policy.should_retry_mutation(err)).await
+}
+
+async fn retry_while<T, F, Fut>(
+    policy: &RetryPolicy,
+    mut op: F,
+    retry: impl Fn(&AlistError) -> bool,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempt = 0;
+    loop {
+        match op().await {
+            Err(err) if attempt < policy.retries && retry(&err) => {
+                let delay = policy.delay_after(&err, attempt);
+                attempt += 1;
+                warn!(
+                    "Request failed ({}); retrying in {:.1}s ({}/{})",
+                    err,
+                    delay.as_secs_f64(),
+                    attempt,
+                    policy.retries
+                );
+                tokio::time::sleep(delay).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_after_in_seconds() {
+        let now = SystemTime::now();
+        assert_eq!(parse_retry_after("120", now), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after(" 0 ", now), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn throttled_waits_as_asked_up_to_the_cap() {
+        let policy = RetryPolicy::default();
+        let throttled = |secs| AlistError::Throttled {
+            status: 429,
+            retry_after: Some(Duration::from_secs(secs)),
+        };
+        assert_eq!(policy.delay_after(&throttled(5), 0), Duration::from_secs(5));
+        assert_eq!(policy.delay_after(&throttled(3600), 0), policy.max_retry_after);
+    }
+
+    #[test]
+    fn retries_only_the_listed_api_codes() {
+        let policy = RetryPolicy {
+            retry_codes: vec![500],
+            ..RetryPolicy::default()
+        };
+        let api = |code| AlistError::Api {
+            code,
+            message: "failed get storage".to_string(),
+        };
+        assert!(policy.should_retry(&api(500)));
+        assert!(!policy.should_retry(&api(403)));
+        assert!(!RetryPolicy::default().should_retry(&api(500)));
+    }
+
+    #[test]
+    fn mutations_are_retried_only_when_the_server_did_not_act() {
+        let policy = RetryPolicy {
+            retry_codes: vec![500],
+            ..RetryPolicy::default()
+        };
+        let failed = AlistError::Api {
+            code: 500,
+            message: "failed to move".to_string(),
+        };
+        let throttled = AlistError::Throttled {
+            status: 503,
+            retry_after: None,
+        };
+        assert!(!policy.should_retry_mutation(&failed));
+        assert!(policy.should_retry_mutation(&throttled));
+        let forced = RetryPolicy { force: true, ..policy };
+        assert!(forced.should_retry_mutation(&failed));
+    }
+}
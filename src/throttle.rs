@@ -0,0 +1,80 @@
+//! Bandwidth limiting for transfers.
+
+use futures_util::stream::{Stream, StreamExt};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Parse a rate such as `500K`, `2M` or `1.5G` (bytes per second, 1024-based
+/// suffixes, optionally followed by `B`).
+pub fn parse_rate(value: &str) -> Result<u64, String> {
+    let upper = value.trim().to_ascii_uppercase();
+    let number = upper.trim_end_matches('B');
+    let (number, multiplier) = match number.chars().last() {
+        Some('K') => (&number[..number.len() - 1], 1u64 << 10),
+        Some('M') => (&number[..number.len() - 1], 1 << 20),
+        Some('G') => (&number[..number.len() - 1], 1 << 30),
+        _ => (number, 1),
+    };
+    let rate = number
+        .parse::<f64>()
+        .map_err(|_| format!("invalid rate `{}`, expected e.g. 500K or 2M", value))?
+        * multiplier as f64;
+    if rate < 1.0 {
+        return Err(format!("rate `{}` must be at least 1 byte per second", value));
+    }
+    Ok(rate as u64)
+}
+
+/// Caps the combined throughput of every stream throttled with it.
+///
+/// Each chunk is held back until the time the limiter would have finished sending
+/// everything before it at the configured rate, so output is paced chunk by chunk
+/// rather than in bursts, and concurrent transfers share the budget.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    bytes_per_sec: u64,
+    next_free: Arc<Mutex<Instant>>,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        RateLimiter {
+            bytes_per_sec: bytes_per_sec.max(1),
+            next_free: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    pub fn bytes_per_sec(&self) -> u64 {
+        self.bytes_per_sec
+    }
+
+    /// Book `len` bytes and return when they may be sent. Unused time while idle
+    /// is not saved up, so a new transfer does not start with a burst.
+    fn reserve(&self, len: usize) -> Instant {
+        let mut next_free = self.next_free.lock().expect("rate limiter lock");
+        let begin = (*next_free).max(Instant::now());
+        *next_free = begin + Duration::from_secs_f64(len as f64 / self.bytes_per_sec as f64);
+        *next_free
+    }
+}
+
+/// Delay the chunks of `stream` to stay within `limiter`, if there is one.
+pub fn throttle<S, B, E>(stream: S, limiter: Option<RateLimiter>) -> impl Stream<Item = Result<B, E>>
+where
+    S: Stream<Item = Result<B, E>>,
+    B: AsRef<[u8]>,
+{
+    stream.then(move |chunk| {
+        let deadline = match (&limiter, &chunk) {
+            (Some(limiter), Ok(bytes)) => Some(limiter.reserve(bytes.as_ref().len())),
+            _ => None,
+        };
+        async move {
+            if let Some(deadline) = deadline {
+                tokio::time::sleep_until(deadline).await;
+            }
+            chunk
+        }
+    })
+}
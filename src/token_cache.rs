@@ -0,0 +1,98 @@
+//! On-disk cache of auth tokens, so repeated invocations can skip logging in.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::Result;
+
+/// How long a cached token is trusted. Alist issues tokens valid for 48 hours
+/// by default, so this stays well inside that.
+pub const TOKEN_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Serialize, Deserialize, Debug)]
+struct CachedToken {
+    token: String,
+    /// Unix timestamp in seconds.
+    expires_at: u64,
+}
+
+/// Tokens keyed by server base URL and username.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct TokenCache {
+    tokens: HashMap<String, CachedToken>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn key(base_url: &str, username: &str) -> String {
+    format!("{}@{}", username, base_url)
+}
+
+impl TokenCache {
+    /// Location of the cache file, e.g. `~/.cache/alist-cli/tokens.json`.
+    pub fn path() -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join("alist-cli").join("tokens.json"))
+    }
+
+    /// Load the cache, treating a missing or unreadable file as empty.
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let data = serde_json::to_vec_pretty(self)?;
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options.open(path)?.write_all(&data)?;
+        Ok(())
+    }
+
+    /// Return the cached token for this server and user if it has not expired.
+    pub fn get(&self, base_url: &str, username: &str) -> Option<&str> {
+        self.tokens
+            .get(&key(base_url, username))
+            .filter(|cached| cached.expires_at > now())
+            .map(|cached| cached.token.as_str())
+    }
+
+    pub fn insert(&mut self, base_url: &str, username: &str, token: &str) {
+        let now = now();
+        self.tokens.retain(|_, cached| cached.expires_at > now);
+        self.tokens.insert(
+            key(base_url, username),
+            CachedToken {
+                token: token.to_string(),
+                expires_at: now + TOKEN_TTL.as_secs(),
+            },
+        );
+    }
+
+    /// Forget the token for this server and user, returning whether one was cached.
+    pub fn remove(&mut self, base_url: &str, username: &str) -> bool {
+        self.tokens.remove(&key(base_url, username)).is_some()
+    }
+}
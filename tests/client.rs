@@ -0,0 +1,985 @@
+//! Exercises `AlistClient` against a fake Alist server.
+
+use alist_cli::client::{ClientOptions, IpFamily, PutOptions};
+use alist_cli::crypt::{self, Passphrase};
+use alist_cli::hash::HashKind;
+use alist_cli::retry::RetryPolicy;
+use alist_cli::{AlistClient, AlistError};
+use indicatif::ProgressBar;
+use reqwest::Method;
+use serde_json::json;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+use wiremock::matchers::{body_json, header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn ok(data: serde_json::Value) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({ "code": 200, "message": "success", "data": data }))
+}
+
+fn api_error(code: u16, message: &str) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({ "code": code, "message": message, "data": null }))
+}
+
+/// A WebDAV `PROPFIND` answer for a file of `size` bytes.
+fn propfind(size: u64) -> ResponseTemplate {
+    ResponseTemplate::new(207).set_body_string(format!(
+        "<?xml version=\"1.0\"?><D:multistatus xmlns:D=\"DAV:\"><D:response><D:propstat><D:prop>\
+         <D:getcontentlength>{}</D:getcontentlength></D:prop></D:propstat></D:response></D:multistatus>",
+        size
+    ))
+}
+
+/// A client for `server` that is already logged in with the token `tok`.
+fn logged_in(server: &MockServer) -> AlistClient {
+    let mut client = AlistClient::new(server.uri());
+    client.set_token("tok");
+    client
+}
+
+#[tokio::test]
+async fn login_sends_credentials_and_keeps_the_token() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/auth/login"))
+        .and(body_json(json!({ "username": "admin", "password": "secret" })))
+        .respond_with(ok(json!({ "token": "abc" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let mut client = AlistClient::new(server.uri());
+    client.login("admin", "secret").await.unwrap();
+    assert_eq!(client.token().as_deref(), Some("abc"));
+}
+
+#[tokio::test]
+async fn login_rejection_is_an_auth_error() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/auth/login"))
+        .respond_with(api_error(400, "password is incorrect"))
+        .mount(&server)
+        .await;
+
+    let mut client = AlistClient::new(server.uri());
+    let err = client.login("admin", "wrong").await.unwrap_err();
+    assert!(matches!(&err, AlistError::Auth { message } if message.contains("password is incorrect")));
+    assert_eq!(client.token(), None);
+}
+
+#[tokio::test]
+async fn login_asks_for_a_two_factor_code_when_the_server_wants_one() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/auth/login"))
+        .and(body_json(json!({ "username": "admin", "password": "secret" })))
+        .respond_with(api_error(402, "Invalid 2FA code"))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/auth/login"))
+        .and(body_json(json!({ "username": "admin", "password": "secret", "otp_code": "123456" })))
+        .respond_with(ok(json!({ "token": "abc" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let mut client = AlistClient::new(server.uri()).with_otp_prompt(|| Ok("123456".to_string()));
+    client.login("admin", "secret").await.unwrap();
+    assert_eq!(client.token().as_deref(), Some("abc"));
+}
+
+#[tokio::test]
+async fn wrong_two_factor_code_is_not_retried() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/auth/login"))
+        .respond_with(api_error(402, "Invalid 2FA code"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let mut client = AlistClient::new(server.uri()).with_otp_prompt(|| panic!("no prompt expected"));
+    let err = client.login_with_otp("admin", "secret", Some("000000")).await.unwrap_err();
+    assert!(matches!(err, AlistError::OtpRequired { .. }));
+}
+
+#[tokio::test]
+async fn a_client_without_a_token_browses_as_a_guest() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/fs/get"))
+        .and(body_json(json!({ "path": "/public/a.txt", "password": "" })))
+        .respond_with(ok(json!({ "name": "a.txt", "size": 5, "is_dir": false })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/fs/get"))
+        .respond_with(api_error(401, "Guest user is disabled, login please"))
+        .mount(&server)
+        .await;
+
+    let client = AlistClient::new(server.uri()).with_anonymous();
+    assert_eq!(client.get("/public/a.txt").await.unwrap().size, 5);
+    let err = client.get("/private/b.txt").await.unwrap_err();
+    assert!(matches!(err, AlistError::Auth { ref message } if message.contains("wants a login")));
+
+    let requests = server.received_requests().await.unwrap();
+    assert!(requests.iter().all(|request| !request.headers.contains_key("Authorization")));
+}
+
+#[tokio::test]
+async fn upload_sends_token_and_encoded_file_path() {
+    let server = MockServer::start().await;
+    Mock::given(method("PUT"))
+        .and(path("/api/fs/put"))
+        .and(header("Authorization", "tok"))
+        .and(header("File-Path", "/my%20dir/%E6%96%87%E4%BB%B6.txt"))
+        .and(header("Content-Length", "5"))
+        .respond_with(ok(json!(null)))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let client = logged_in(&server);
+    let contents: &[u8] = b"hello";
+    client
+        .put_reader(contents, Some(5), "/my dir/文件.txt", &PutOptions::default(), &ProgressBar::hidden())
+        .await
+        .unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests[0].body, b"hello");
+}
+
+#[tokio::test]
+async fn token_goes_in_a_custom_header_when_asked() {
+    let server = MockServer::start().await;
+    Mock::given(method("PUT"))
+        .and(path("/api/fs/put"))
+        .and(header("X-Auth-Token", "Bearer tok"))
+        .respond_with(ok(json!(null)))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let token_header = "X-Auth-Token".parse().unwrap();
+    let client = logged_in(&server).with_auth_scheme("Bearer").with_token_header(token_header);
+    let contents: &[u8] = b"hello";
+    client
+        .put_reader(contents, Some(5), "/a.txt", &PutOptions::default(), &ProgressBar::hidden())
+        .await
+        .unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    assert!(!requests[0].headers.contains_key("Authorization"));
+}
+
+#[tokio::test]
+async fn encrypted_uploads_send_only_ciphertext_and_download_decrypted() {
+    let server = MockServer::start().await;
+    let size = crypt::encrypted_size(5).to_string();
+    Mock::given(method("PUT"))
+        .and(path("/api/fs/put"))
+        .and(header("Content-Length", size.as_str()))
+        .respond_with(ok(json!(null)))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let client = logged_in(&server);
+    let passphrase = Passphrase::new("secret");
+    let options = PutOptions {
+        encrypt: Some(passphrase.clone()),
+        ..PutOptions::default()
+    };
+    let contents: &[u8] = b"hello";
+    client
+        .put_reader(contents, Some(5), "/a.txt.enc", &options, &ProgressBar::hidden())
+        .await
+        .unwrap();
+    let sent = server.received_requests().await.unwrap()[0].body.clone();
+    assert!(!sent.windows(5).any(|window| window == b"hello"));
+
+    Mock::given(method("GET"))
+        .and(path("/d/a.txt.enc"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(sent))
+        .mount(&server)
+        .await;
+    let url = format!("{}/d/a.txt.enc", server.uri());
+    let download = client.open_raw(&url, 0).await.unwrap().decrypted(Some(passphrase));
+    let mut plain = Vec::new();
+    download.write_to(&mut plain, &ProgressBar::hidden()).await.unwrap();
+    assert_eq!(plain, b"hello");
+}
+
+#[tokio::test]
+async fn upload_sends_modification_time_in_milliseconds() {
+    let server = MockServer::start().await;
+    Mock::given(method("PUT"))
+        .and(path("/api/fs/put"))
+        .and(header("Last-Modified", "1700000000123"))
+        .respond_with(ok(json!(null)))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let client = logged_in(&server);
+    let options = PutOptions {
+        modified: Some(UNIX_EPOCH + Duration::from_millis(1_700_000_000_123)),
+        ..PutOptions::default()
+    };
+    let contents: &[u8] = b"hello";
+    client
+        .put_reader(contents, Some(5), "/file.txt", &options, &ProgressBar::hidden())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn upload_sends_the_file_hash_header() {
+    let server = MockServer::start().await;
+    Mock::given(method("PUT"))
+        .and(path("/api/fs/put"))
+        .and(header("X-File-Md5", "5d41402abc4b2a76b9719d911017c592"))
+        .respond_with(ok(json!(null)))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let client = logged_in(&server);
+    let options = PutOptions {
+        file_hash: Some((HashKind::Md5, "5d41402abc4b2a76b9719d911017c592".to_string())),
+        ..PutOptions::default()
+    };
+    let contents: &[u8] = b"hello";
+    client
+        .put_reader(contents, Some(5), "/file.txt", &options, &ProgressBar::hidden())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn upload_failure_code_is_an_api_error() {
+    let server = MockServer::start().await;
+    Mock::given(method("PUT"))
+        .and(path("/api/fs/put"))
+        .respond_with(api_error(500, "failed to upload: storage not found"))
+        .mount(&server)
+        .await;
+
+    let client = logged_in(&server);
+    let contents: &[u8] = b"hello";
+    let err = client
+        .put_reader(contents, Some(5), "/file.txt", &PutOptions::default(), &ProgressBar::hidden())
+        .await
+        .unwrap_err();
+    assert!(matches!(err, AlistError::Api { code: 500, .. }));
+}
+
+#[tokio::test]
+async fn api_error_codes_propagate() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/fs/mkdir"))
+        .and(header("Authorization", "tok"))
+        .and(body_json(json!({ "path": "/new" })))
+        .respond_with(api_error(403, "permission denied"))
+        .mount(&server)
+        .await;
+
+    let err = logged_in(&server).mkdir("/new").await.unwrap_err();
+    assert!(matches!(&err, AlistError::Api { code: 403, message } if message == "permission denied"));
+}
+
+#[tokio::test]
+async fn api_errors_quote_the_request_id() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/fs/mkdir"))
+        .respond_with(api_error(403, "permission denied").insert_header("X-Request-Id", "req-42"))
+        .mount(&server)
+        .await;
+
+    let err = logged_in(&server).mkdir("/new").await.unwrap_err();
+    assert!(matches!(
+        &err,
+        AlistError::Api { code: 403, message } if message == "permission denied [request ID req-42]"
+    ));
+}
+
+#[tokio::test]
+async fn http_errors_and_bodies_not_from_alist_quote_the_request_id() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/fs/mkdir"))
+        .respond_with(ResponseTemplate::new(502).insert_header("X-Request-Id", "req-7"))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/fs/remove"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("oops").insert_header("Cf-Ray", "ray-1"))
+        .mount(&server)
+        .await;
+
+    let client = logged_in(&server).with_retry(RetryPolicy {
+        retries: 0,
+        ..RetryPolicy::default()
+    });
+    let err = client.mkdir("/new").await.unwrap_err();
+    assert!(matches!(&err, AlistError::Request { request_id, .. } if request_id == "req-7"));
+    assert!(matches!(err.root(), AlistError::Http(err) if err.status().is_some_and(|status| status == 502)));
+    let err = client.remove("/", &["a.txt".to_string()]).await.unwrap_err();
+    assert!(matches!(&err, AlistError::Api { message, .. } if message.ends_with("[request ID ray-1]")));
+}
+
+#[tokio::test]
+async fn a_missing_object_does_not_exist() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/fs/get"))
+        .respond_with(api_error(500, "failed get storage: object not found"))
+        .mount(&server)
+        .await;
+
+    assert!(!logged_in(&server).exists("/missing.txt").await.unwrap());
+}
+
+#[tokio::test]
+async fn requests_without_a_token_fail_before_sending() {
+    let server = MockServer::start().await;
+    let client = AlistClient::new(server.uri());
+    let err = client.mkdir("/new").await.unwrap_err();
+    assert!(matches!(err, AlistError::Auth { .. }));
+    assert!(server.received_requests().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn an_expired_token_is_renewed_once() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/fs/mkdir"))
+        .and(header("Authorization", "tok"))
+        .respond_with(api_error(401, "token is expired"))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/auth/login"))
+        .and(body_json(json!({ "username": "admin", "password": "secret" })))
+        .respond_with(ok(json!({ "token": "fresh" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/fs/mkdir"))
+        .and(header("Authorization", "fresh"))
+        .respond_with(ok(json!(null)))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let renewed = Arc::new(Mutex::new(None));
+    let seen = renewed.clone();
+    let client = logged_in(&server).with_relogin(
+        "admin",
+        || Ok("secret".to_string()),
+        move |token| *seen.lock().unwrap() = Some(token.to_string()),
+    );
+    client.mkdir("/new").await.unwrap();
+    assert_eq!(client.token().as_deref(), Some("fresh"));
+    assert_eq!(renewed.lock().unwrap().as_deref(), Some("fresh"));
+}
+
+#[tokio::test]
+async fn a_token_rejected_twice_is_an_auth_error() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/fs/mkdir"))
+        .respond_with(api_error(401, "token is invalidated"))
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/auth/login"))
+        .respond_with(ok(json!({ "token": "fresh" })))
+        .mount(&server)
+        .await;
+
+    let client = logged_in(&server).with_relogin("admin", || Ok("secret".to_string()), |_| {});
+    let err = client.mkdir("/new").await.unwrap_err();
+    assert!(matches!(err, AlistError::Auth { .. }));
+}
+
+#[tokio::test]
+async fn webdav_upload_uses_basic_auth() {
+    let server = MockServer::start().await;
+    Mock::given(method("PUT"))
+        .and(path("/dav/my%20dir/a.txt"))
+        .and(header("Authorization", "Basic YWRtaW46c2VjcmV0"))
+        .respond_with(ResponseTemplate::new(201))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let client = AlistClient::new(server.uri()).with_webdav("admin", "secret");
+    let contents: &[u8] = b"hello";
+    client
+        .put_reader(contents, Some(5), "/my dir/a.txt", &PutOptions::default(), &ProgressBar::hidden())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn parts_of_a_file_are_put_with_their_range() {
+    let server = MockServer::start().await;
+    Mock::given(method("PUT"))
+        .and(path("/dav/big.bin"))
+        .and(header("Content-Range", "bytes 2-4/5"))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("PROPFIND"))
+        .and(path("/dav/big.bin"))
+        .respond_with(propfind(5))
+        .mount(&server)
+        .await;
+    Mock::given(method("PUT"))
+        .and(path("/dav/plain.bin"))
+        .respond_with(ResponseTemplate::new(400))
+        .mount(&server)
+        .await;
+
+    let local = std::env::temp_dir().join(format!("alist-cli-part-{}", std::process::id()));
+    std::fs::write(&local, b"hello").unwrap();
+    let client = AlistClient::new(server.uri()).with_webdav("admin", "secret");
+    let progress = ProgressBar::hidden();
+    let stored = client.put_file_range(&local, "/big.bin", 2..5, 5, &progress).await;
+    let refused = client.put_file_range(&local, "/plain.bin", 0..2, 5, &progress).await;
+    let over_api = logged_in(&server).put_file_range(&local, "/big.bin", 0..2, 5, &progress).await;
+    std::fs::remove_file(&local).unwrap();
+    assert!(stored.unwrap());
+    assert!(!refused.unwrap());
+    assert!(!over_api.unwrap());
+}
+
+#[tokio::test]
+async fn parts_a_server_stores_as_the_whole_file_count_as_refused() {
+    let server = MockServer::start().await;
+    Mock::given(method("PUT"))
+        .and(path("/dav/big.bin"))
+        .respond_with(ResponseTemplate::new(201))
+        .mount(&server)
+        .await;
+    // Like golang.org/x/net/webdav, the server keeps only the body of the last PUT.
+    Mock::given(method("PROPFIND"))
+        .and(path("/dav/big.bin"))
+        .respond_with(propfind(3))
+        .mount(&server)
+        .await;
+
+    let local = std::env::temp_dir().join(format!("alist-cli-ignored-range-{}", std::process::id()));
+    std::fs::write(&local, b"hello").unwrap();
+    let client = AlistClient::new(server.uri()).with_webdav("admin", "secret");
+    let stored = client.put_file_range(&local, "/big.bin", 2..5, 5, &ProgressBar::hidden()).await;
+    std::fs::remove_file(&local).unwrap();
+    assert!(!stored.unwrap());
+}
+
+#[tokio::test]
+async fn raw_urls_on_other_hosts_get_no_token_or_server_headers() {
+    let server = MockServer::start().await;
+    let cdn = MockServer::start().await;
+    for mock_server in [&server, &cdn] {
+        Mock::given(method("GET"))
+            .and(path("/file.bin"))
+            .and(header("X-Cdn-Key", "k"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("data"))
+            .expect(1)
+            .mount(mock_server)
+            .await;
+    }
+
+    let options = ClientOptions {
+        headers: vec![("x-proxy-auth".parse().unwrap(), "p".parse().unwrap())],
+        raw_headers: vec![("x-cdn-key".parse().unwrap(), "k".parse().unwrap())],
+        ..ClientOptions::default()
+    };
+    let mut client = AlistClient::with_options(server.uri(), &options).unwrap();
+    client.set_token("tok");
+    let mut contents = Vec::new();
+    for base in [server.uri(), cdn.uri()] {
+        let url = format!("{}/file.bin", base);
+        client.download_raw(&url, &mut contents, &ProgressBar::hidden()).await.unwrap();
+    }
+    assert_eq!(contents, b"datadata");
+
+    let on_server = &server.received_requests().await.unwrap()[0];
+    assert_eq!(on_server.headers.get("authorization").unwrap(), "tok");
+    assert_eq!(on_server.headers.get("x-proxy-auth").unwrap(), "p");
+    let on_cdn = &cdn.received_requests().await.unwrap()[0];
+    assert!(on_cdn.headers.get("authorization").is_none());
+    assert!(on_cdn.headers.get("x-proxy-auth").is_none());
+}
+
+#[tokio::test]
+async fn input_shorter_than_its_size_fails_the_upload() {
+    let server = MockServer::start().await;
+    Mock::given(method("PUT"))
+        .and(path("/api/fs/put"))
+        .respond_with(ok(json!(null)))
+        .mount(&server)
+        .await;
+
+    let client = logged_in(&server);
+    let contents: &[u8] = b"hel";
+    let result = client
+        .put_reader(contents, Some(5), "/file.txt", &PutOptions::default(), &ProgressBar::hidden())
+        .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn rate_limited_request_is_retried_after_the_servers_hint() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/public/settings"))
+        .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/public/settings"))
+        .respond_with(ok(json!({ "version": "v3.40.0" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let settings = AlistClient::new(server.uri()).public_settings().await.unwrap();
+    assert_eq!(settings.version, "v3.40.0");
+}
+
+#[tokio::test]
+async fn html_instead_of_json_is_reported_with_the_body() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/auth/login"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("<html><body>Bad gateway</body></html>"))
+        .mount(&server)
+        .await;
+
+    let mut client = AlistClient::new(server.uri());
+    let err = client.login("admin", "secret").await.unwrap_err();
+    assert!(matches!(&err, AlistError::Api { code: 200, message } if message.contains("Bad gateway")));
+}
+
+#[tokio::test]
+async fn servers_that_are_not_alist_are_recognized() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/auth/login"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/public/settings"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "version": "1.0" })))
+        .mount(&server)
+        .await;
+
+    let mut client = AlistClient::new(server.uri());
+    let err = client.login("admin", "secret").await.unwrap_err();
+    assert!(matches!(&err, AlistError::Api { code: 404, message } if message.contains("Alist API endpoint")));
+    let err = client.public_settings().await.unwrap_err();
+    assert!(matches!(&err, AlistError::Api { code: 200, message } if message.contains("`code` field")));
+}
+
+#[tokio::test]
+async fn raw_requests_send_the_token_and_return_the_body() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/fs/dirs"))
+        .and(header("Authorization", "tok"))
+        .and(body_json(json!({ "path": "/" })))
+        .respond_with(ok(json!([{ "name": "docs" }])))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let client = logged_in(&server);
+    let (status, body) = client
+        .request_raw(Method::POST, "/api/fs/dirs", Some(&json!({ "path": "/" })))
+        .await
+        .unwrap();
+    assert_eq!(status, 200);
+    assert!(body.contains("docs"));
+}
+
+#[tokio::test]
+async fn redirected_login_is_an_auth_error_naming_the_target() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/auth/login"))
+        .respond_with(ResponseTemplate::new(301).insert_header("Location", "/alist/api/auth/login"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/alist/api/auth/login"))
+        .respond_with(ResponseTemplate::new(405))
+        .mount(&server)
+        .await;
+
+    let mut client = AlistClient::new(server.uri());
+    let err = client.login("admin", "secret").await.unwrap_err();
+    let expected = format!("use {}/alist as the server URL", server.uri());
+    assert!(matches!(&err, AlistError::Auth { message } if message.contains(&expected)));
+}
+
+#[tokio::test]
+async fn empty_login_response_is_an_api_error() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/auth/login"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&server)
+        .await;
+
+    let mut client = AlistClient::new(server.uri());
+    let err = client.login("admin", "secret").await.unwrap_err();
+    assert!(matches!(&err, AlistError::Api { code: 200, message } if message.contains("empty response")));
+}
+
+#[tokio::test]
+async fn logout_reports_servers_without_the_endpoint() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/auth/logout"))
+        .and(header("Authorization", "tok"))
+        .respond_with(ResponseTemplate::new(404))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    assert!(!logged_in(&server).logout().await.unwrap());
+}
+
+#[tokio::test]
+async fn existing_directories_are_told_apart_from_files() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/fs/get"))
+        .and(body_json(json!({ "path": "/docs", "password": "" })))
+        .respond_with(ok(json!({ "name": "docs", "size": 0, "is_dir": true, "raw_url": "" })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/fs/get"))
+        .and(body_json(json!({ "path": "/docs/new.txt", "password": "" })))
+        .respond_with(api_error(500, "object not found"))
+        .mount(&server)
+        .await;
+
+    let client = logged_in(&server);
+    assert!(client.is_dir("/docs").await.unwrap());
+    assert!(!client.is_dir("/docs/new.txt").await.unwrap());
+}
+
+#[tokio::test]
+async fn files_are_the_same_when_size_and_hash_match() {
+    let server = MockServer::start().await;
+    let file = |name: &str, size: u64, md5: &str| {
+        Mock::given(method("POST"))
+            .and(path("/api/fs/get"))
+            .and(body_json(json!({ "path": name, "password": "" })))
+            .respond_with(ok(json!({
+                "name": name, "size": size, "is_dir": false, "hash_info": { "md5": md5 }
+            })))
+    };
+    file("/same.txt", 5, "5D41402ABC4B2A76B9719D911017C592").mount(&server).await;
+    file("/edited.txt", 5, "00000000000000000000000000000000").mount(&server).await;
+    file("/longer.txt", 6, "5d41402abc4b2a76b9719d911017c592").mount(&server).await;
+    file("/unhashed.txt", 5, "").mount(&server).await;
+    Mock::given(method("POST"))
+        .and(path("/api/fs/get"))
+        .respond_with(api_error(500, "object not found"))
+        .mount(&server)
+        .await;
+
+    let local = std::env::temp_dir().join(format!("alist-cli-same-{}", std::process::id()));
+    std::fs::write(&local, b"hello").unwrap();
+    let client = logged_in(&server);
+    let mut same = Vec::new();
+    for remote in ["/same.txt", "/edited.txt", "/longer.txt", "/unhashed.txt", "/missing.txt"] {
+        same.push(client.has_same_file(remote, &local, Some(HashKind::Md5)).await.unwrap());
+    }
+    std::fs::remove_file(&local).unwrap();
+    assert_eq!(same, [true, false, false, false, false]);
+}
+
+#[tokio::test]
+async fn the_strongest_reported_hash_is_picked() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/fs/get"))
+        .respond_with(ok(json!({
+            "name": "a.txt", "size": 5, "is_dir": false,
+            "hash_info": { "md5": "5d41", "sha1": "", "sha256": "2cf2", "gcid": "ab12" }
+        })))
+        .mount(&server)
+        .await;
+
+    let hashes = logged_in(&server).get("/a.txt").await.unwrap().hash_info.unwrap();
+    assert_eq!(hashes.pick(None), Some((HashKind::Sha256, "2cf2".to_string())));
+    assert_eq!(hashes.strongest(&[HashKind::Md5, HashKind::Sha1]), Some((HashKind::Md5, "5d41")));
+    assert_eq!(hashes.strongest(&[HashKind::Sha1]), None);
+    assert_eq!(hashes.reported(), ["gcid", "md5", "sha256"]);
+}
+
+#[tokio::test]
+async fn waiting_for_a_file_refreshes_the_listing_until_it_shows_up() {
+    let server = MockServer::start().await;
+    let listing = |names: &[&str]| {
+        let content: Vec<_> = names.iter().map(|name| json!({ "name": name, "is_dir": false })).collect();
+        ok(json!({ "content": content, "total": names.len() }))
+    };
+    Mock::given(method("POST"))
+        .and(path("/api/fs/list"))
+        .and(body_json(json!({ "path": "/up", "password": "", "page": 1, "per_page": 0, "refresh": true })))
+        .respond_with(listing(&["old.txt"]))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/fs/list"))
+        .respond_with(listing(&["new.txt", "old.txt"]))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let client = logged_in(&server);
+    assert!(client.wait_listed("/up/new.txt", Duration::from_secs(10)).await.unwrap());
+}
+
+#[tokio::test]
+async fn folder_password_is_sent_with_get() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/fs/get"))
+        .and(body_json(json!({ "path": "/private/a.txt", "password": "open sesame" })))
+        .respond_with(ok(json!({ "name": "a.txt", "size": 5, "is_dir": false, "raw_url": "" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let client = logged_in(&server).with_dir_password("open sesame");
+    assert_eq!(client.get("/private/a.txt").await.unwrap().name, "a.txt");
+}
+
+#[tokio::test]
+async fn folder_password_is_asked_for_when_refused() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/fs/list"))
+        .and(body_json(json!({
+            "path": "/private",
+            "password": "",
+            "page": 1,
+            "per_page": 0,
+            "refresh": false
+        })))
+        .respond_with(api_error(403, "password is incorrect or you have no permission"))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/fs/list"))
+        .and(body_json(json!({
+            "path": "/private",
+            "password": "secret",
+            "page": 1,
+            "per_page": 0,
+            "refresh": false
+        })))
+        .respond_with(ok(json!({ "content": [], "total": 0 })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let client = logged_in(&server).with_dir_password_prompt(|| Ok("secret".to_string()));
+    assert!(client.list("/private").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn listed_api_codes_are_retried() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/fs/list"))
+        .respond_with(api_error(500, "failed get storage: timeout"))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/fs/list"))
+        .respond_with(ok(json!({ "content": [], "total": 0 })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let client = logged_in(&server).with_retry(RetryPolicy {
+        retries: 1,
+        base_delay: Duration::ZERO,
+        retry_codes: vec![500],
+        ..RetryPolicy::default()
+    });
+    assert!(client.list("/").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn removals_that_reached_the_server_are_not_retried() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/fs/remove"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let client = logged_in(&server).with_retry(RetryPolicy {
+        base_delay: Duration::ZERO,
+        ..RetryPolicy::default()
+    });
+    let err = client.remove("/", &["a.txt".to_string()]).await.unwrap_err();
+    assert!(matches!(&err, AlistError::Http(err) if err.status().is_some_and(|status| status == 500)));
+}
+
+#[tokio::test]
+async fn raw_requests_that_may_change_something_are_not_retried_after_a_timeout() {
+    // A server that takes every request and never answers, counting the connections.
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let connections = Arc::new(AtomicUsize::new(0));
+    let counted = connections.clone();
+    tokio::spawn(async move {
+        let mut open = Vec::new();
+        while let Ok((socket, _)) = listener.accept().await {
+            counted.fetch_add(1, Ordering::SeqCst);
+            open.push(socket);
+        }
+    });
+
+    let options = ClientOptions {
+        timeout: Some(Duration::from_millis(200)),
+        ..ClientOptions::default()
+    };
+    let mut client = AlistClient::with_options(base_url, &options).unwrap().with_retry(RetryPolicy {
+        retries: 1,
+        base_delay: Duration::ZERO,
+        ..RetryPolicy::default()
+    });
+    client.set_token("tok");
+    let body = json!({ "dir": "/", "names": ["a.txt"] });
+    let requests = [
+        (Method::POST, "/api/fs/remove", 1),
+        (Method::POST, "/api/fs/list", 1),
+        (Method::GET, "/api/me", 2),
+    ];
+    for (method, endpoint, attempts) in requests {
+        connections.store(0, Ordering::SeqCst);
+        let body = (method == Method::POST).then_some(&body);
+        let err = client.request_raw(method, endpoint, body).await.unwrap_err();
+        assert!(matches!(&err, AlistError::Http(err) if err.is_timeout()));
+        assert_eq!(connections.load(Ordering::SeqCst), attempts, "{}", endpoint);
+    }
+}
+
+#[tokio::test]
+async fn connects_from_the_local_address_over_its_ip_version() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/public/settings"))
+        .respond_with(ok(json!({ "version": "v3.40.0" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let options = ClientOptions {
+        local_address: Some([127, 0, 0, 1].into()),
+        ip_family: Some(IpFamily::V4),
+        ..ClientOptions::default()
+    };
+    let base_url = format!("http://localhost:{}", server.address().port());
+    let client = AlistClient::with_options(base_url, &options).unwrap();
+    assert_eq!(client.public_settings().await.unwrap().version, "v3.40.0");
+}
+
+#[tokio::test]
+async fn webhooks_are_posted_without_the_token() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/hooks/upload"))
+        .and(body_json(json!({ "status": "ok", "path": "/dir/a.txt" })))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let client = logged_in(&server);
+    let url = format!("{}/hooks/upload", server.uri());
+    client
+        .post_webhook(&url, &json!({ "status": "ok", "path": "/dir/a.txt" }))
+        .await
+        .unwrap();
+    let requests = server.received_requests().await.unwrap();
+    assert!(requests[0].headers.get("authorization").is_none());
+}
+
+#[tokio::test]
+async fn storages_need_an_admin_account() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/admin/storage/list"))
+        .and(header("authorization", "tok"))
+        .respond_with(ok(json!({
+            "content": [
+                { "id": 1, "mount_path": "/local", "driver": "Local", "status": "work", "disabled": false }
+            ],
+            "total": 1
+        })))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/admin/storage/list"))
+        .respond_with(api_error(403, "You are not an admin"))
+        .mount(&server)
+        .await;
+
+    let client = logged_in(&server);
+    let storages = client.storages().await.unwrap();
+    assert_eq!(storages.len(), 1);
+    assert_eq!((storages[0].mount_path.as_str(), storages[0].driver.as_str()), ("/local", "Local"));
+
+    let err = client.storages().await.unwrap_err();
+    assert!(matches!(err, AlistError::Auth { message } if message.contains("admin account")));
+}
+
+#[tokio::test]
+async fn storages_are_disabled_by_id() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/admin/storage/disable"))
+        .and(query_param("id", "3"))
+        .respond_with(ok(json!(null)))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    logged_in(&server).set_storage_enabled(3, false).await.unwrap();
+}